@@ -0,0 +1,242 @@
+//! Files/sec of the scanner on a synthetic tree, next to the same scan with the per-file
+//! mutex bookkeeping it used before the shared scan state moved to atomics.
+//!
+//! Run with `cargo bench --bench scan_throughput`. The tree (1M files by default) is generated
+//! once under the temp dir and reused; set `SCAN_BENCH_FILES` to change its size or
+//! `SCAN_BENCH_DIR` to scan an existing directory instead.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use disk_analyzer_lib::{scan_tree, FileInfo, NodeKind, NoProgress, ScanOptions};
+use globset::{GlobSet, GlobSetBuilder};
+use rayon::prelude::*;
+
+const FILES_PER_DIR: usize = 1000;
+const DIRS_PER_GROUP: usize = 100;
+
+fn main() {
+    let root = match std::env::var("SCAN_BENCH_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => {
+            let files = std::env::var("SCAN_BENCH_FILES")
+                .ok()
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(1_000_000);
+            synthetic_tree(files)
+        }
+    };
+
+    // Warm the dentry and inode caches so both runs measure bookkeeping rather than the disk
+    run_atomic(&root);
+
+    let (before_files, before_time) = run_mutex_baseline(&root);
+    let (after_files, after_time) = run_atomic(&root);
+
+    println!("tree: {}", root.display());
+    report("mutex counters (before)", before_files, before_time);
+    report("atomic scan state (after)", after_files, after_time);
+}
+
+fn report(label: &str, files: usize, elapsed: Duration) {
+    let rate = files as f64 / elapsed.as_secs_f64();
+    println!("{:<28} {:>9} files in {:>8.2?}  {:>12.0} files/sec", label, files, elapsed, rate);
+}
+
+fn run_atomic(root: &Path) -> (usize, Duration) {
+    let options = ScanOptions::default();
+    let cancel_flag = AtomicBool::new(false);
+
+    let start = Instant::now();
+    let result = scan_tree(root, 0, &options, None, &cancel_flag, &NoProgress).expect("scan failed");
+    (result.file_count, start.elapsed())
+}
+
+fn run_mutex_baseline(root: &Path) -> (usize, Duration) {
+    let state = MutexState {
+        files: Mutex::new(0),
+        size: Mutex::new(0),
+        last_progress: Mutex::new(Instant::now()),
+        inodes: Mutex::new(HashMap::new()),
+        // An empty exclude set, like the default options give the scanner
+        excludes: GlobSetBuilder::new().build().expect("empty glob set"),
+    };
+
+    let start = Instant::now();
+    let root_node = mutex_baseline(root, &state).expect("scan failed");
+    let elapsed = start.elapsed();
+    assert_eq!(root_node.file_count, *state.files.lock().unwrap());
+    let files = *state.files.lock().unwrap();
+    (files, elapsed)
+}
+
+/// Shared state of the scanner before it moved to atomics.
+struct MutexState {
+    files: Mutex<usize>,
+    size: Mutex<u64>,
+    last_progress: Mutex<Instant>,
+    inodes: Mutex<HashMap<(u64, u64), usize>>,
+    excludes: GlobSet,
+}
+
+/// The scanner as it was before the atomics: the same walk, filtering, hard-link tracking and
+/// tree building, but both counters and the progress timestamp behind mutexes, taken on
+/// every file.
+fn mutex_baseline(path: &Path, state: &MutexState) -> Option<FileInfo> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    let link_target = metadata
+        .file_type()
+        .is_symlink()
+        .then(|| fs::read_link(path).map(|target| target.to_string_lossy().to_string()).unwrap_or_default());
+    let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().to_string();
+    let path_str = path.to_string_lossy().to_string();
+    let mtime = mtime_ns(&metadata);
+    let ctime = ctime_ns(&metadata);
+
+    if !metadata.is_dir() {
+        let inode = hard_link_key(&metadata);
+        let hard_link_duplicate = match inode {
+            Some(key) => {
+                let mut seen = state.inodes.lock().unwrap();
+                let links = seen.entry(key).or_insert(0);
+                *links += 1;
+                *links > 1
+            }
+            None => false,
+        };
+        let (apparent_size, allocated_size) = if hard_link_duplicate {
+            (0, 0)
+        } else {
+            (metadata.len(), allocated_size(&metadata))
+        };
+        let file_count = if link_target.is_some() { 0 } else { 1 };
+        *state.files.lock().unwrap() += file_count;
+        *state.size.lock().unwrap() += apparent_size;
+
+        let mut last_update = state.last_progress.lock().unwrap();
+        if last_update.elapsed().as_millis() > 50 {
+            let _progress = (path_str.clone(), *state.files.lock().unwrap(), *state.size.lock().unwrap());
+            *last_update = Instant::now();
+        }
+        drop(last_update);
+
+        return Some(FileInfo {
+            name,
+            path: path_str,
+            size: apparent_size,
+            apparent_size,
+            allocated_size,
+            is_dir: false,
+            kind: if link_target.is_some() { NodeKind::Symlink } else { NodeKind::File },
+            file_count,
+            child_count: 0,
+            hard_link_duplicate,
+            link_target,
+            mtime,
+            ctime,
+            inode,
+            children: Vec::new(),
+        });
+    }
+
+    let entries: Vec<PathBuf> = fs::read_dir(path)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter(|e| !state.excludes.is_match(e.file_name()) && !state.excludes.is_match(e.path()))
+        .map(|e| e.path())
+        .collect();
+
+    let mut children: Vec<FileInfo> = entries
+        .par_iter()
+        .filter_map(|child| mutex_baseline(child, state))
+        .collect();
+    children.sort_by_key(|child| Reverse(child.size));
+
+    Some(FileInfo {
+        name,
+        path: path_str,
+        size: children.iter().map(|child| child.size).sum(),
+        apparent_size: children.iter().map(|child| child.apparent_size).sum(),
+        allocated_size: children.iter().map(|child| child.allocated_size).sum(),
+        is_dir: true,
+        kind: NodeKind::Directory,
+        file_count: children.iter().map(|child| child.file_count).sum(),
+        child_count: children.len(),
+        hard_link_duplicate: false,
+        link_target,
+        mtime,
+        ctime,
+        inode: None,
+        children,
+    })
+}
+
+fn mtime_ns(metadata: &fs::Metadata) -> Option<i64> {
+    let since_epoch = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    i64::try_from(since_epoch.as_nanos()).ok()
+}
+
+#[cfg(unix)]
+fn ctime_ns(metadata: &fs::Metadata) -> Option<i64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ctime() * 1_000_000_000 + metadata.ctime_nsec())
+}
+
+#[cfg(not(unix))]
+fn ctime_ns(_metadata: &fs::Metadata) -> Option<i64> {
+    None
+}
+
+#[cfg(unix)]
+fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+#[cfg(unix)]
+fn hard_link_key(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn hard_link_key(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Creates `files` small files as group/dir/file, reusing a previous tree of the same size.
+fn synthetic_tree(files: usize) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("disk-analyzer-bench-{}", files));
+    let marker = root.join(".complete");
+    if marker.exists() {
+        return root;
+    }
+
+    let dirs = files.div_ceil(FILES_PER_DIR);
+    (0..dirs).into_par_iter().for_each(|dir| {
+        let dir_path = root
+            .join(format!("group{:04}", dir / DIRS_PER_GROUP))
+            .join(format!("dir{:04}", dir % DIRS_PER_GROUP));
+        fs::create_dir_all(&dir_path).expect("cannot create bench directory");
+
+        let in_this_dir = FILES_PER_DIR.min(files - dir * FILES_PER_DIR);
+        for file in 0..in_this_dir {
+            // Vary sizes a little so the tree isn't all empty files
+            let contents = vec![b'x'; file % 64];
+            fs::write(dir_path.join(format!("file{:04}.dat", file)), contents).expect("cannot create bench file");
+        }
+    });
+
+    fs::write(&marker, b"").expect("cannot mark bench tree complete");
+    root
+}
@@ -1,3 +1,5 @@
 fn main() {
+    // Only the desktop app needs the Tauri context; the CLI builds without it
+    #[cfg(feature = "gui")]
     tauri_build::build()
 }
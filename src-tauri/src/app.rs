@@ -0,0 +1,744 @@
+//! The desktop app: Tauri commands and the state they share between calls.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::audit::{self, AuditEntry, AuditFilter};
+use crate::delete::{
+    self, DeleteError, DeleteMode, DeletionPlan, DeletionProgress, DeletionReport, ItemResult, PlannedItem,
+};
+use crate::diff::{self, DiffNode, SnapshotDiff};
+use crate::export::{self, ExportFormat};
+use crate::history::{self, History, Operation, OperationKind, TrashedEntry, UndoReport};
+use crate::import;
+use crate::protect::{self, ProtectedPath, ProtectedPaths, ProtectionReason};
+use crate::scanner::{
+    counted_inodes, directory_count, display_view, find_node, per_second, replace_node, scan_tree, shallow_copy, EntryScanner,
+    EstimateMethod, FileInfo, ProgressSink, ScanOptions, ScanPhase, ScanProgress, ScanResult, ScanSummary, SortKey,
+    sort_children,
+};
+use crate::snapshot::{self, SnapshotMeta};
+use crate::watch::{self, WatchStatus};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChildrenPage {
+    pub path: String,
+    /// Total number of children, independent of the requested page
+    pub total: usize,
+    pub offset: usize,
+    pub children: Vec<FileInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiffChildrenPage {
+    pub path: String,
+    pub total: usize,
+    pub offset: usize,
+    pub children: Vec<DiffNode>,
+}
+
+
+// Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
+#[tauri::command]
+fn greet(name: &str) -> String {
+    format!("Hello, {}! You've been greeted from Rust!", name)
+}
+
+/// Tracks running scans so they can be cancelled from the frontend, and keeps finished
+/// scans with their full tree so views can page through it with `get_children`.
+#[derive(Default)]
+pub struct ScanRegistry {
+    next_id: AtomicU64,
+    active: Mutex<HashMap<u64, Arc<AtomicBool>>>,
+    scans: Mutex<HashMap<u64, Arc<StoredScan>>>,
+    watchers: Mutex<HashMap<u64, watch::WatchHandle>>,
+}
+
+/// A finished scan with its full tree. The result sits behind a lock so a watcher can keep
+/// it up to date while views read from it.
+pub struct StoredScan {
+    pub(crate) options: ScanOptions,
+    pub(crate) result: RwLock<ScanResult>,
+    /// Multiply linked files the tree counts, so entries read again don't count them twice
+    counted_inodes: Mutex<HashSet<(u64, u64)>>,
+}
+
+impl StoredScan {
+    fn new(options: ScanOptions, result: ScanResult) -> Self {
+        StoredScan {
+            options,
+            counted_inodes: Mutex::new(counted_inodes(&result.root)),
+            result: RwLock::new(result),
+        }
+    }
+
+    /// Brings each of `paths` in the tree in line with the filesystem: read again as the scan
+    /// would have read it, or dropped when it no longer exists. No path should be inside
+    /// another, which would be read twice. Returns the paths whose entry changed.
+    pub(crate) fn refresh(&self, paths: &[&Path]) -> Vec<PathBuf> {
+        let root = PathBuf::from(&self.result.read().unwrap().root.path);
+        let paths: Vec<&Path> = paths
+            .iter()
+            .copied()
+            .filter(|path| *path != root && path.starts_with(&root))
+            .collect();
+        if paths.is_empty() {
+            return Vec::new();
+        }
+
+        let mut counted = self.counted_inodes.lock().unwrap();
+        // Whatever the old entries counted is up for grabs again, so a rewritten hard link
+        // keeps its bytes
+        let previous: Vec<HashSet<(u64, u64)>> = {
+            let result = self.result.read().unwrap();
+            paths
+                .iter()
+                .map(|path| find_node(&result.root, path).map(counted_inodes).unwrap_or_default())
+                .collect()
+        };
+        for key in previous.iter().flatten() {
+            counted.remove(key);
+        }
+
+        // Scanned without holding the tree's lock, so views stay responsive. The outer `None`
+        // leaves an entry as it is, the inner one removes it
+        let replacements: Vec<Option<Option<FileInfo>>> = match EntryScanner::new(&root, &self.options, &counted) {
+            Ok(scanner) => paths
+                .iter()
+                .map(|path| match fs::symlink_metadata(path) {
+                    Ok(metadata) if scanner.excludes(path, metadata.is_dir()) => None,
+                    Ok(_) => scanner.scan(path).map(Some),
+                    Err(_) => Some(None),
+                })
+                .collect(),
+            Err(_) => vec![None; paths.len()],
+        };
+
+        let mut changed = Vec::new();
+        let mut result = self.result.write().unwrap();
+        for ((path, previous), replacement) in paths.into_iter().zip(previous).zip(replacements) {
+            let Some(replacement) = replacement else {
+                counted.extend(previous);
+                continue;
+            };
+            let removed_directories = find_node(&result.root, path).map_or(0, directory_count);
+            let added_directories = replacement.as_ref().map_or(0, directory_count);
+            let added = replacement.as_ref().map(counted_inodes).unwrap_or_default();
+            if !replace_node(&mut result.root, path, replacement) {
+                counted.extend(previous);
+                continue;
+            }
+            result.directory_count = (result.directory_count + added_directories).saturating_sub(removed_directories);
+            counted.extend(added);
+            changed.push(path.to_path_buf());
+        }
+        result.total_size = result.root.size;
+        result.file_count = result.root.file_count;
+        changed
+    }
+}
+
+impl ScanRegistry {
+    fn allocate_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    fn get(&self, scan_id: u64) -> Result<Arc<StoredScan>, String> {
+        self.scans.lock().unwrap().get(&scan_id).cloned()
+            .ok_or_else(|| format!("No scan with id {}", scan_id))
+    }
+
+    /// Brings `path` up to date in every stored scan that contains it, after it was deleted
+    /// or reappeared on disk.
+    fn refresh_path(&self, path: &Path) {
+        let scans: Vec<Arc<StoredScan>> = self.scans.lock().unwrap().values().cloned().collect();
+        for scan in scans {
+            scan.refresh(&[path]);
+        }
+    }
+
+    /// Keeps the full result and returns the copy for the frontend, whose tree is trimmed to
+    /// the depth and display limit in `options`.
+    fn store(&self, mut result: ScanResult, options: &ScanOptions) -> ScanResult {
+        let view = display_view(&result.root, options.initial_depth, options.display_limit);
+        let tree = std::mem::replace(&mut result.root, view);
+        let stored = StoredScan::new(options.clone(), ScanResult { root: tree, ..result.clone() });
+        self.scans.lock().unwrap().insert(result.scan_id, Arc::new(stored));
+        result
+    }
+}
+
+#[tauri::command]
+async fn scan_directory(path: String, options: Option<ScanOptions>, app_handle: AppHandle, registry: State<'_, ScanRegistry>) -> Result<ScanResult, String> {
+    run_scan(&path, options.unwrap_or_default(), None, app_handle, &registry).await
+}
+
+/// Rescans the root of a saved snapshot, reading only the directories that changed since.
+/// A snapshot taken with other filters is scanned again in full.
+#[tauri::command]
+async fn rescan_incremental(
+    snapshot_id: String,
+    options: Option<ScanOptions>,
+    app_handle: AppHandle,
+    registry: State<'_, ScanRegistry>,
+) -> Result<ScanResult, String> {
+    let (meta, baseline) = snapshot::load(&snapshot_dir(&app_handle)?, &snapshot_id).map_err(|e| e.to_string())?;
+    // A cancelled scan left directories half listed, and unchanged timestamps would keep them so
+    if baseline.cancelled {
+        return Err("Snapshot is of a cancelled scan and can't be rescanned incrementally".to_string());
+    }
+    // A baseline listed with other filters would carry their entries over, so it can only be
+    // read again from scratch
+    let options = options.unwrap_or_default();
+    let reusable = baseline.options.as_ref().is_some_and(|used| used.lists_same_entries(&options));
+    let previous = reusable.then_some(&baseline.root);
+    run_scan(&meta.root_path, options, previous, app_handle, &registry).await
+}
+
+/// Runs a scan from start to finish: registers it for cancellation, emits the lifecycle events
+/// and keeps the full result for paging.
+async fn run_scan(
+    path: &str,
+    options: ScanOptions,
+    previous: Option<&FileInfo>,
+    app_handle: AppHandle,
+    registry: &ScanRegistry,
+) -> Result<ScanResult, String> {
+    let scan_id = registry.allocate_id();
+    let cancel_flag = Arc::new(AtomicBool::new(false));
+    registry.active.lock().unwrap().insert(scan_id, cancel_flag.clone());
+
+    // Let the frontend know the ID before any progress arrives so it can cancel early
+    let _ = app_handle.emit("scan-started", scan_id);
+
+    let result = scan_directory_impl(path, scan_id, &options, previous, &cancel_flag, app_handle.clone()).await.map_err(|e| e.to_string());
+    registry.active.lock().unwrap().remove(&scan_id);
+    let result = result?;
+
+    let serialize_start = Instant::now();
+    let _ = app_handle.emit("scan-progress", &finished_progress(&result, ScanPhase::Serializing));
+
+    // Only a trimmed view crosses the IPC boundary; the full tree stays here for paging
+    let mut result = registry.store(result, &options);
+
+    result.timing.serialize_ms = serialize_start.elapsed().as_millis() as u64;
+    result.timing.total_ms += result.timing.serialize_ms;
+    let _ = app_handle.emit("scan-complete", &ScanSummary {
+        scan_id,
+        cancelled: result.cancelled,
+        file_count: result.file_count,
+        directory_count: result.directory_count,
+        total_size: result.total_size,
+        error_count: result.error_count,
+        files_per_sec: per_second(result.file_count as f64, result.timing.total_ms),
+        bytes_per_sec: per_second(result.total_size as f64, result.timing.total_ms),
+        timing: result.timing.clone(),
+    });
+
+    Ok(result)
+}
+
+/// Progress event for the phases after the walk, when the counters are final.
+fn finished_progress(result: &ScanResult, phase: ScanPhase) -> ScanProgress {
+    let elapsed_ms = result.timing.total_ms;
+    ScanProgress {
+        scan_id: result.scan_id,
+        phase,
+        current_path: result.root.path.clone(),
+        files_processed: result.file_count,
+        directories_processed: result.directory_count,
+        errors_so_far: result.error_count,
+        total_size_so_far: result.total_size,
+        elapsed_ms,
+        files_per_sec: per_second(result.file_count as f64, elapsed_ms),
+        bytes_per_sec: per_second(result.total_size as f64, elapsed_ms),
+        eta_ms: None,
+        estimated_total: None,
+        estimated_total_bytes: None,
+        percent_complete: None,
+        estimate_method: EstimateMethod::None,
+    }
+}
+
+#[tauri::command]
+fn cancel_scan(scan_id: u64, registry: State<'_, ScanRegistry>) -> Result<(), String> {
+    match registry.active.lock().unwrap().get(&scan_id) {
+        Some(flag) => {
+            flag.store(true, Ordering::Relaxed);
+            Ok(())
+        }
+        None => Err(format!("No active scan with id {}", scan_id)),
+    }
+}
+
+async fn scan_directory_impl(
+    root_path: &str,
+    scan_id: u64,
+    options: &ScanOptions,
+    previous: Option<&FileInfo>,
+    cancel_flag: &AtomicBool,
+    app_handle: AppHandle,
+) -> Result<ScanResult, Box<dyn std::error::Error>> {
+    scan_tree(Path::new(root_path), scan_id, options, previous, cancel_flag, &EventSink(app_handle))
+}
+
+/// Forwards scan progress to the frontend as `scan-progress` events.
+struct EventSink(AppHandle);
+
+impl ProgressSink for EventSink {
+    fn progress(&self, progress: &ScanProgress) {
+        let _ = self.0.emit("scan-progress", progress);
+    }
+}
+
+#[tauri::command]
+fn get_children(
+    scan_id: u64,
+    path: String,
+    offset: usize,
+    limit: usize,
+    sort: Option<SortKey>,
+    registry: State<'_, ScanRegistry>,
+) -> Result<ChildrenPage, String> {
+    let scan = registry.get(scan_id)?;
+    let result = scan.result.read().unwrap();
+    let node = find_node(&result.root, Path::new(&path))
+        .ok_or_else(|| format!("Path not found in scan: {}", path))?;
+
+    let mut children: Vec<&FileInfo> = node.children.iter().collect();
+    sort_children(&mut children, sort.unwrap_or_default());
+
+    Ok(ChildrenPage {
+        path: node.path.clone(),
+        total: children.len(),
+        offset,
+        children: children.into_iter().skip(offset).take(limit).map(shallow_copy).collect(),
+    })
+}
+
+/// Drops the stored tree of a finished scan once the frontend no longer needs it.
+#[tauri::command]
+fn release_scan(scan_id: u64, registry: State<'_, ScanRegistry>) {
+    registry.watchers.lock().unwrap().remove(&scan_id);
+    registry.scans.lock().unwrap().remove(&scan_id);
+}
+
+/// Keeps a finished scan's tree in sync with the filesystem until `stop_watch` or
+/// `release_scan`, emitting `tree-updated` after each batch of changes.
+#[tauri::command]
+fn start_watch(scan_id: u64, app_handle: AppHandle, registry: State<'_, ScanRegistry>) -> Result<WatchStatus, String> {
+    let scan = registry.get(scan_id)?;
+    let mut watchers = registry.watchers.lock().unwrap();
+    if let Some(existing) = watchers.get(&scan_id) {
+        return Ok(existing.status.clone());
+    }
+
+    let handle = watch::start(scan_id, scan, app_handle)?;
+    let status = handle.status.clone();
+    watchers.insert(scan_id, handle);
+    Ok(status)
+}
+
+#[tauri::command]
+fn stop_watch(scan_id: u64, registry: State<'_, ScanRegistry>) {
+    registry.watchers.lock().unwrap().remove(&scan_id);
+}
+
+fn snapshot_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("snapshots"))
+        .map_err(|e| e.to_string())
+}
+
+/// Writes a finished scan, including everything the views trimmed, to the app data directory.
+#[tauri::command]
+async fn save_snapshot(
+    scan_id: u64,
+    name: Option<String>,
+    app_handle: AppHandle,
+    registry: State<'_, ScanRegistry>,
+) -> Result<SnapshotMeta, String> {
+    let scan = registry.get(scan_id)?;
+    let result = scan.result.read().unwrap();
+    snapshot::save(&snapshot_dir(&app_handle)?, name, &result).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn list_snapshots(app_handle: AppHandle) -> Result<Vec<SnapshotMeta>, String> {
+    snapshot::list(&snapshot_dir(&app_handle)?).map_err(|e| e.to_string())
+}
+
+/// Reopens a saved snapshot as a new scan, so it can be browsed just like a fresh one.
+#[tauri::command]
+async fn load_snapshot(
+    snapshot_id: String,
+    options: Option<ScanOptions>,
+    app_handle: AppHandle,
+    registry: State<'_, ScanRegistry>,
+) -> Result<ScanResult, String> {
+    let (_, mut result) = snapshot::load(&snapshot_dir(&app_handle)?, &snapshot_id).map_err(|e| e.to_string())?;
+    result.scan_id = registry.allocate_id();
+    Ok(registry.store(result, &options.unwrap_or_default()))
+}
+
+/// Diffs the frontend is paging through, by id, until `release_diff`.
+#[derive(Default)]
+pub struct DiffRegistry {
+    next_id: AtomicU64,
+    diffs: Mutex<HashMap<u64, Arc<DiffNode>>>,
+}
+
+/// Shows what grew, shrank, appeared or disappeared between two saved snapshots. Only
+/// `depth` levels are returned; deeper ones come from `get_diff_children`.
+#[tauri::command]
+async fn diff_snapshots(
+    a: String,
+    b: String,
+    depth: Option<usize>,
+    app_handle: AppHandle,
+    diffs: State<'_, DiffRegistry>,
+) -> Result<SnapshotDiff, String> {
+    let dir = snapshot_dir(&app_handle)?;
+    let (from, old) = snapshot::load(&dir, &a).map_err(|e| e.to_string())?;
+    let (to, new) = snapshot::load(&dir, &b).map_err(|e| e.to_string())?;
+
+    if from.root_path != to.root_path {
+        return Err(format!(
+            "Snapshots are of different folders: {} and {}",
+            from.root_path, to.root_path
+        ));
+    }
+    if old.size_mode != new.size_mode {
+        return Err("Snapshots were taken with different size modes".to_string());
+    }
+
+    let tree = diff::diff_trees(&old.root, &new.root);
+    let root = diff::diff_view(&tree, depth.unwrap_or(ScanOptions::default().initial_depth));
+    let diff_id = diffs.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+    diffs.diffs.lock().unwrap().insert(diff_id, Arc::new(tree));
+
+    Ok(SnapshotDiff { diff_id, from, to, root })
+}
+
+#[tauri::command]
+fn get_diff_children(
+    diff_id: u64,
+    path: String,
+    offset: usize,
+    limit: usize,
+    diffs: State<'_, DiffRegistry>,
+) -> Result<DiffChildrenPage, String> {
+    let tree = diffs
+        .diffs
+        .lock()
+        .unwrap()
+        .get(&diff_id)
+        .cloned()
+        .ok_or_else(|| format!("No diff with id {}", diff_id))?;
+    let node = diff::find_diff_node(&tree, Path::new(&path))
+        .ok_or_else(|| format!("Path not found in diff: {}", path))?;
+
+    Ok(DiffChildrenPage {
+        path: node.path.clone(),
+        total: node.children.len(),
+        offset,
+        children: node
+            .children
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .map(|child| diff::diff_view(child, 0))
+            .collect(),
+    })
+}
+
+#[tauri::command]
+fn release_diff(diff_id: u64, diffs: State<'_, DiffRegistry>) {
+    diffs.diffs.lock().unwrap().remove(&diff_id);
+}
+
+/// Opens an `ncdu -o` export or a `du -ab` listing as a new scan, so it can be browsed, saved
+/// and diffed just like a fresh one.
+#[tauri::command]
+async fn import_scan(
+    path: String,
+    options: Option<ScanOptions>,
+    registry: State<'_, ScanRegistry>,
+) -> Result<ScanResult, String> {
+    let options = options.unwrap_or_default();
+    let mut result = import::import(Path::new(&path), options.size_mode).map_err(|e| e.to_string())?;
+    result.scan_id = registry.allocate_id();
+    Ok(registry.store(result, &options))
+}
+
+/// Writes a scan's complete tree, including everything the views trimmed, to `dest_path`.
+#[tauri::command]
+async fn export_scan(
+    scan_id: u64,
+    format: ExportFormat,
+    dest_path: String,
+    registry: State<'_, ScanRegistry>,
+) -> Result<(), String> {
+    let scan = registry.get(scan_id)?;
+    let result = scan.result.read().unwrap();
+    export::export(&result, format, Path::new(&dest_path)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn format_bytes(bytes: u64) -> String {
+    human_bytes::human_bytes(bytes as f64)
+}
+
+#[tauri::command]
+async fn open_in_explorer(path: String) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        std::process::Command::new("explorer")
+            .arg("/select,")
+            .arg(&path)
+            .spawn()
+            .map_err(|e| e.to_string())?;
+    }
+    
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open")
+            .arg("-R")
+            .arg(&path)
+            .spawn()
+            .map_err(|e| e.to_string())?;
+    }
+    
+    #[cfg(target_os = "linux")]
+    {
+        // Try to use the default file manager
+        let parent = Path::new(&path).parent()
+            .ok_or("Could not get parent directory")?;
+        std::process::Command::new("xdg-open")
+            .arg(parent)
+            .spawn()
+            .map_err(|e| e.to_string())?;
+    }
+    
+    Ok(())
+}
+
+/// The deletion plan waiting to be carried out, from `plan_deletion` until `execute_deletion`.
+/// Only the latest one is kept: planning again replaces a plan the user walked away from.
+#[derive(Default)]
+pub struct DeletionPlans {
+    next_id: AtomicU64,
+    latest: Mutex<Option<DeletionPlan>>,
+}
+
+/// Reports what deleting `paths` would involve, without touching anything. Items are moved
+/// to the trash unless `mode` asks for permanent deletion explicitly.
+#[tauri::command]
+async fn plan_deletion(
+    paths: Vec<String>,
+    mode: Option<DeleteMode>,
+    app_handle: AppHandle,
+    registry: State<'_, ScanRegistry>,
+    plans: State<'_, DeletionPlans>,
+) -> Result<DeletionPlan, String> {
+    let protected = protected_paths(&app_handle, &registry).map_err(|e| e.to_string())?;
+    let mut plan = delete::plan_deletion(&paths, mode.unwrap_or_default(), &protected);
+    plan.plan_id = plans.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+    *plans.latest.lock().unwrap() = Some(plan.clone());
+    Ok(plan)
+}
+
+/// Carries out a plan from `plan_deletion`, emitting `deletion-progress` after each item, and
+/// drops the deleted entries from the stored scans. A plan can only run once.
+#[tauri::command]
+async fn execute_deletion(
+    plan_id: u64,
+    app_handle: AppHandle,
+    registry: State<'_, ScanRegistry>,
+    plans: State<'_, DeletionPlans>,
+    history: State<'_, Mutex<History>>,
+) -> Result<DeletionReport, String> {
+    let plan = {
+        let mut latest = plans.latest.lock().unwrap();
+        match latest.take() {
+            Some(plan) if plan.plan_id == plan_id => plan,
+            other => {
+                *latest = other;
+                return Err(format!("No deletion plan with id {}, or a newer one replaced it", plan_id));
+            }
+        }
+    };
+    let protected = protected_paths(&app_handle, &registry).map_err(|e| e.to_string())?;
+
+    // Logged item by item, so what was done is on record even if the app doesn't get to the end
+    let mut audit_error = None;
+    let mut report = delete::execute_plan(
+        &plan,
+        &protected,
+        &mut |progress: &DeletionProgress, item: &PlannedItem, result: &ItemResult| {
+            if let Some(entry) = audit::plan_item_entry(plan.mode, item, result) {
+                if let Err(e) = record_audit(&app_handle, &[entry]) {
+                    audit_error.get_or_insert(e);
+                }
+            }
+            let _ = app_handle.emit("deletion-progress", progress);
+        },
+    );
+    report.audit_error = audit_error;
+    let mut trashed = Vec::new();
+    for result in &report.results {
+        if let ItemResult::Deleted { outcome } = result {
+            registry.refresh_path(Path::new(&outcome.path));
+            if let Some(trash_path) = &outcome.trash_path {
+                trashed.push(TrashedEntry {
+                    original: outcome.path.clone(),
+                    trash_path: trash_path.clone(),
+                    bytes: outcome.bytes_freed,
+                });
+            }
+        }
+    }
+    history.lock().unwrap().record(OperationKind::Trash, trashed);
+    Ok(report)
+}
+
+/// Operations that can be undone, newest first.
+#[tauri::command]
+fn get_operation_history(history: State<'_, Mutex<History>>) -> Vec<Operation> {
+    history.lock().unwrap().list()
+}
+
+/// Restores what the most recent operation moved to the trash and puts it back into the
+/// stored scans. If an original location has been taken again, nothing is restored and the
+/// conflicts come back with suggested names; calling again with `rename_conflicts` restores
+/// those items under them.
+#[tauri::command]
+async fn undo_last_operation(
+    rename_conflicts: Option<bool>,
+    app_handle: AppHandle,
+    registry: State<'_, ScanRegistry>,
+    history: State<'_, Mutex<History>>,
+) -> Result<UndoReport, String> {
+    let operation = history.lock().unwrap().pop_last().ok_or("Nothing to undo")?;
+    let items = operation.items.clone();
+    let (mut report, remaining) = history::undo(operation, rename_conflicts.unwrap_or(false));
+    report.audit_error = record_audit(&app_handle, &audit::restore_entries(&items, &report)).err();
+    if let Some(remaining) = remaining {
+        history.lock().unwrap().push_back(remaining);
+    }
+
+    for item in &report.restored {
+        registry.refresh_path(Path::new(&item.restored_to));
+    }
+    Ok(report)
+}
+
+fn audit_log_file(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join(audit::LOG_FILE))
+        .map_err(|e| e.to_string())
+}
+
+/// Appends to the audit log. The operations have already happened by now, so a log that
+/// can't be written goes into the command's result rather than failing the command.
+fn record_audit(app_handle: &AppHandle, entries: &[AuditEntry]) -> Result<(), String> {
+    let file = audit_log_file(app_handle)?;
+    audit::append(&file, entries).map_err(|e| format!("Could not write the audit log: {}", e))
+}
+
+/// Entries of the audit log matching `filter`, newest first.
+#[tauri::command]
+fn get_audit_log(filter: Option<AuditFilter>, app_handle: AppHandle) -> Result<Vec<AuditEntry>, String> {
+    let file = audit_log_file(&app_handle)?;
+    audit::read(&file, &filter.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+/// Everything destructive commands must leave alone: the built-in list, the user's additions
+/// and the roots of the scans the app holds. If the user's list can't be read, nothing is
+/// deleted rather than deleting with a partial list.
+fn protected_paths(app_handle: &AppHandle, registry: &ScanRegistry) -> Result<ProtectedPaths, DeleteError> {
+    let mut protected = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())
+        .and_then(|dir| ProtectedPaths::load(&dir).map_err(|e| e.to_string()))
+        .map_err(|message| DeleteError::Io {
+            path: "protected paths".to_string(),
+            message,
+        })?;
+    for scan in registry.scans.lock().unwrap().values() {
+        protected.add(&scan.result.read().unwrap().root.path, ProtectionReason::ScanRoot);
+    }
+    Ok(protected)
+}
+
+#[tauri::command]
+fn get_protected_paths(app_handle: AppHandle, registry: State<'_, ScanRegistry>) -> Result<Vec<ProtectedPath>, String> {
+    protected_paths(&app_handle, &registry)
+        .map(|protected| protected.list())
+        .map_err(|e| e.to_string())
+}
+
+/// Replaces the user's protected paths. Only absolute paths are accepted.
+#[tauri::command]
+fn set_protected_paths(paths: Vec<String>, app_handle: AppHandle) -> Result<(), String> {
+    let dir = app_handle.path().app_data_dir().map_err(|e| e.to_string())?;
+    protect::write_user_paths(&dir, &paths).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn copy_to_clipboard(text: String) -> Result<(), String> {
+    // This is a simple implementation - in a real app you might want to use a clipboard crate
+    let _ = text;
+    Ok(())
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        .manage(ScanRegistry::default())
+        .manage(DeletionPlans::default())
+        .manage(DiffRegistry::default())
+        .manage(Mutex::new(History::default()))
+        .invoke_handler(tauri::generate_handler![
+            greet, 
+            scan_directory, 
+            cancel_scan,
+            get_children,
+            release_scan,
+            save_snapshot,
+            list_snapshots,
+            load_snapshot,
+            diff_snapshots,
+            get_diff_children,
+            release_diff,
+            export_scan,
+            import_scan,
+            rescan_incremental,
+            start_watch,
+            stop_watch,
+            format_bytes, 
+            open_in_explorer, 
+            plan_deletion,
+            execute_deletion,
+            get_operation_history,
+            undo_last_operation,
+            get_audit_log,
+            get_protected_paths,
+            set_protected_paths,
+            copy_to_clipboard
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
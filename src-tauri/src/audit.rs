@@ -0,0 +1,289 @@
+//! A local record of every file operation the app carries out, for shared machines where
+//! someone has to be able to tell who removed what.
+//!
+//! The log is a JSON-lines file that is only ever opened for appending: entries are added
+//! one line at a time and never rewritten or removed by the app. A line that can't be parsed,
+//! say one cut short by a crash, is skipped when the log is read.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::delete::{DeleteError, DeleteMode, DeleteOutcome, ItemResult, PlannedItem};
+use crate::history::{TrashedEntry, UndoReport};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+    /// Moved to the trash
+    Trash,
+    /// Removed for good
+    Delete,
+    /// Moved back out of the trash by an undo
+    Restore,
+}
+
+impl From<DeleteMode> for AuditOperation {
+    fn from(mode: DeleteMode) -> Self {
+        match mode {
+            DeleteMode::Trash => AuditOperation::Trash,
+            DeleteMode::Permanent => AuditOperation::Delete,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum AuditResult {
+    Success,
+    Failed { message: String },
+    /// The app declined to act, e.g. on a protected path
+    Refused { reason: String },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub user: String,
+    pub operation: AuditOperation,
+    pub path: String,
+    /// Bytes the item took up on disk
+    pub size: u64,
+    /// Where the item ended up, for trash and restore
+    pub destination: Option<String>,
+    pub result: AuditResult,
+}
+
+impl AuditEntry {
+    /// An entry stamped with the current time and user.
+    pub fn new(operation: AuditOperation, path: impl Into<String>, size: u64, result: AuditResult) -> Self {
+        AuditEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            user: current_user(),
+            operation,
+            path: path.into(),
+            size,
+            destination: None,
+            result,
+        }
+    }
+}
+
+/// Which entries `read` returns. Every field left out matches everything.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct AuditFilter {
+    pub operation: Option<AuditOperation>,
+    /// Only entries for this path or anything below it
+    pub path: Option<String>,
+    pub user: Option<String>,
+    /// Seconds since the Unix epoch, inclusive
+    pub since: Option<u64>,
+    /// Seconds since the Unix epoch, inclusive
+    pub until: Option<u64>,
+    /// Only successful entries when true, only failed or refused ones when false
+    pub succeeded: Option<bool>,
+    /// Keep only the newest this many matches
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.operation.is_none_or(|operation| entry.operation == operation)
+            && self
+                .path
+                .as_ref()
+                .is_none_or(|path| Path::new(&entry.path).starts_with(path))
+            && self.user.as_ref().is_none_or(|user| &entry.user == user)
+            && self.since.is_none_or(|since| entry.timestamp >= since)
+            && self.until.is_none_or(|until| entry.timestamp <= until)
+            && self
+                .succeeded
+                .is_none_or(|succeeded| (entry.result == AuditResult::Success) == succeeded)
+    }
+}
+
+/// Name of the log in the app data directory. Every frontend appends to the same one.
+pub const LOG_FILE: &str = "audit.log";
+
+/// Appends `entries` to the log at `log_file`, creating it if needed. Each entry goes out in
+/// a single write, so concurrent writers don't interleave within a line.
+pub fn append(log_file: &Path, entries: &[AuditEntry]) -> io::Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    if let Some(dir) = log_file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().append(true).create(true).open(log_file)?;
+    for entry in entries {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        file.write_all(&line)?;
+    }
+    file.sync_data()
+}
+
+/// The entries in the log at `log_file` matching `filter`, newest first. A missing log is
+/// an empty one.
+pub fn read(log_file: &Path, filter: &AuditFilter) -> io::Result<Vec<AuditEntry>> {
+    let file = match File::open(log_file) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut entries = Vec::new();
+    // Bytes rather than lines, so a line cut off mid-character is skipped like any other
+    for line in BufReader::new(file).split(b'\n') {
+        let Ok(entry) = serde_json::from_slice::<AuditEntry>(&line?) else {
+            continue;
+        };
+        if filter.matches(&entry) {
+            entries.push(entry);
+        }
+    }
+    entries.reverse();
+    if let Some(limit) = filter.limit {
+        entries.truncate(limit);
+    }
+    Ok(entries)
+}
+
+/// The entry for deleting `path`, which took up `size` bytes, however that went. Refusing a
+/// protected path is logged as such rather than as a failure.
+pub fn deletion_entry(
+    mode: DeleteMode,
+    path: &str,
+    size: u64,
+    result: Result<&DeleteOutcome, &DeleteError>,
+) -> AuditEntry {
+    let operation = AuditOperation::from(mode);
+    match result {
+        Ok(outcome) => AuditEntry {
+            destination: outcome.trash_path.clone(),
+            ..AuditEntry::new(operation, &outcome.path, outcome.bytes_freed, AuditResult::Success)
+        },
+        Err(DeleteError::Protected(error)) => {
+            AuditEntry::new(operation, path, size, AuditResult::Refused { reason: error.to_string() })
+        }
+        Err(error) => AuditEntry::new(operation, path, size, AuditResult::Failed { message: error.to_string() }),
+    }
+}
+
+/// The entry for one item of an executed plan, or `None` for an item removed along with
+/// another selection containing it: it is logged with that one.
+pub fn plan_item_entry(mode: DeleteMode, item: &PlannedItem, result: &ItemResult) -> Option<AuditEntry> {
+    // A covered item is never protected itself, as its cover would be too, so skipping it
+    // only ever means its cover was removed
+    if item.covered_by.is_some() && matches!(result, ItemResult::Skipped { .. }) {
+        return None;
+    }
+    Some(match result {
+        ItemResult::Deleted { outcome } => deletion_entry(mode, &item.path, item.bytes, Ok(outcome)),
+        ItemResult::Failed { path, error } => deletion_entry(mode, path, item.bytes, Err(error)),
+        ItemResult::Skipped { path, reason } => AuditEntry::new(
+            AuditOperation::from(mode),
+            path,
+            item.bytes,
+            AuditResult::Refused { reason: reason.clone() },
+        ),
+    })
+}
+
+/// One entry per item an undo restored or failed to restore. Conflicts that stopped the
+/// undo before anything was moved aren't logged.
+pub fn restore_entries(items: &[TrashedEntry], report: &UndoReport) -> Vec<AuditEntry> {
+    let size_of = |original: &str| {
+        items
+            .iter()
+            .find(|item| item.original == original)
+            .map_or(0, |item| item.bytes)
+    };
+    let restored = report.restored.iter().map(|item| AuditEntry {
+        destination: Some(item.restored_to.clone()),
+        ..AuditEntry::new(AuditOperation::Restore, &item.original, size_of(&item.original), AuditResult::Success)
+    });
+    let failed = report.failed.iter().map(|failure| {
+        AuditEntry::new(
+            AuditOperation::Restore,
+            &failure.path,
+            size_of(&failure.path),
+            AuditResult::Failed { message: failure.message.clone() },
+        )
+    });
+    restored.chain(failed).collect()
+}
+
+/// The account the app runs as, looked up from the real user id rather than trusting
+/// `$USER`. Falls back to the numeric id when it has no name.
+#[cfg(unix)]
+pub fn current_user() -> String {
+    use std::ffi::CStr;
+
+    // SAFETY: getuid cannot fail; getpwuid_r only writes into the zeroed passwd struct and
+    // the buffer whose length it is given, and pw_name points into that buffer on success
+    unsafe {
+        let uid = libc::getuid();
+        let mut passwd: libc::passwd = std::mem::zeroed();
+        let mut buffer = vec![0 as libc::c_char; 1024];
+        let mut found: *mut libc::passwd = std::ptr::null_mut();
+        let status = libc::getpwuid_r(uid, &mut passwd, buffer.as_mut_ptr(), buffer.len(), &mut found);
+        if status == 0 && !found.is_null() && !passwd.pw_name.is_null() {
+            return CStr::from_ptr(passwd.pw_name).to_string_lossy().to_string();
+        }
+        uid.to_string()
+    }
+}
+
+#[cfg(not(unix))]
+pub fn current_user() -> String {
+    std::env::var("USERNAME").unwrap_or_else(|_| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn read_skips_lines_cut_short() {
+        let scratch = TempDir::new().unwrap();
+        let log_file = scratch.path().join(LOG_FILE);
+        let entries = [
+            AuditEntry::new(AuditOperation::Trash, "/data/first", 1, AuditResult::Success),
+            AuditEntry::new(AuditOperation::Delete, "/data/second", 2, AuditResult::Success),
+        ];
+        append(&log_file, &entries).unwrap();
+
+        // A crash in the middle of writing a path with a multi-byte character
+        let mut torn = serde_json::to_vec(&AuditEntry::new(
+            AuditOperation::Trash,
+            "/data/caf\u{e9}",
+            3,
+            AuditResult::Success,
+        ))
+        .unwrap();
+        let cut = torn.iter().position(|&byte| byte == 0xc3).unwrap() + 1;
+        torn.truncate(cut);
+        let mut file = OpenOptions::new().append(true).open(&log_file).unwrap();
+        file.write_all(&torn).unwrap();
+
+        let read_back = read(&log_file, &AuditFilter::default()).unwrap();
+        let paths: Vec<&str> = read_back.iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(paths, vec!["/data/second", "/data/first"]);
+    }
+
+    #[test]
+    fn a_missing_log_is_empty() {
+        let scratch = TempDir::new().unwrap();
+        assert!(read(&scratch.path().join(LOG_FILE), &AuditFilter::default()).unwrap().is_empty());
+    }
+}
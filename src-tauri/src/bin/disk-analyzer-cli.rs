@@ -0,0 +1,207 @@
+//! Headless front end to the scan engine, for servers and scripts. It scans with the same
+//! options and trims the tree the same way as the desktop app, so the numbers match.
+
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::AtomicBool;
+
+use clap::{Parser, ValueEnum};
+use disk_analyzer_lib::export::csv_field;
+use disk_analyzer_lib::{
+    display_view, scan_tree, FileInfo, NoProgress, ProgressSink, ScanOptions, ScanProgress,
+    ScanResult, SizeMode, SymlinkPolicy,
+};
+use human_bytes::human_bytes;
+
+#[derive(Parser)]
+#[command(name = "disk-analyzer-cli", version, about = "Show what is using disk space under a directory")]
+struct Args {
+    /// Directory to scan
+    root: PathBuf,
+
+    /// Levels of the tree to print below the root
+    #[arg(short, long, default_value_t = 1)]
+    depth: usize,
+
+    /// Entries shown per directory, the rest are folded into "N other items" (0 shows all)
+    #[arg(short = 'n', long, default_value_t = 20)]
+    top: usize,
+
+    /// Skip entries matching a glob; a pattern with a `/` is matched against the whole path
+    #[arg(short, long = "exclude", value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Only count files with these extensions
+    #[arg(long = "include-ext", value_name = "EXT")]
+    include_extensions: Vec<String>,
+
+    /// Report blocks allocated on disk instead of apparent sizes
+    #[arg(long)]
+    allocated: bool,
+
+    /// Don't cross into other filesystems
+    #[arg(short = 'x', long)]
+    one_file_system: bool,
+
+    /// Follow symbolic links
+    #[arg(short = 'L', long)]
+    follow_symlinks: bool,
+
+    #[arg(short, long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+
+    /// Don't draw progress on stderr
+    #[arg(short, long)]
+    quiet: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+impl Args {
+    fn scan_options(&self) -> ScanOptions {
+        ScanOptions {
+            display_limit: self.top,
+            initial_depth: self.depth,
+            size_mode: if self.allocated { SizeMode::Allocated } else { SizeMode::Apparent },
+            one_file_system: self.one_file_system,
+            symlinks: if self.follow_symlinks { SymlinkPolicy::Follow } else { SymlinkPolicy::DontFollow },
+            exclude: self.exclude.clone(),
+            include_extensions: self.include_extensions.clone(),
+        }
+    }
+}
+
+/// Redraws a single status line on stderr, so stdout stays clean for piping.
+struct StderrProgress;
+
+impl ProgressSink for StderrProgress {
+    fn progress(&self, progress: &ScanProgress) {
+        let percent = progress
+            .percent_complete
+            .map(|percent| format!(" ({:.0}%)", percent))
+            .unwrap_or_default();
+        eprint!(
+            "\r\x1b[K{} files, {}{}  {}",
+            progress.files_processed,
+            human_bytes(progress.total_size_so_far as f64),
+            percent,
+            progress.current_path,
+        );
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let options = args.scan_options();
+    let show_progress = !args.quiet && io::stderr().is_terminal();
+
+    let cancel_flag = AtomicBool::new(false);
+    let sink: &dyn ProgressSink = if show_progress { &StderrProgress } else { &NoProgress };
+    let scanned = scan_tree(&args.root, 0, &options, None, &cancel_flag, sink);
+    if show_progress {
+        eprint!("\r\x1b[K");
+    }
+
+    let mut result = match scanned {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("disk-analyzer-cli: {}: {}", args.root.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+    result.root = display_view(&result.root, options.initial_depth, options.display_limit);
+
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    let written = match args.format {
+        Format::Table => write_table(&mut out, &result),
+        Format::Json => serde_json::to_writer_pretty(&mut out, &result)
+            .map_err(io::Error::from)
+            .and_then(|_| writeln!(out)),
+        Format::Csv => write_csv(&mut out, &result),
+    };
+    if let Err(e) = written.and_then(|_| out.flush()) {
+        // A closed pipe (`| head`) isn't worth complaining about
+        if e.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("disk-analyzer-cli: {}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    for group in &result.error_groups {
+        eprintln!("disk-analyzer-cli: {} entries skipped: {:?}", group.count, group.kind);
+    }
+    ExitCode::SUCCESS
+}
+
+fn write_table(out: &mut impl Write, result: &ScanResult) -> io::Result<()> {
+    write_rows(out, &result.root, result.root.size, 0)?;
+    writeln!(
+        out,
+        "\n{} in {} files and {} directories, scanned in {} ms",
+        human_bytes(result.total_size as f64),
+        result.file_count,
+        result.directory_count,
+        result.timing.total_ms,
+    )?;
+    if result.excluded_entries > 0 {
+        writeln!(out, "{} entries ({}) excluded", result.excluded_entries, human_bytes(result.excluded_bytes as f64))?;
+    }
+    if result.error_count > 0 {
+        writeln!(out, "{} entries could not be read", result.error_count)?;
+        if let Some(hidden) = result.unreadable_bytes_estimate.filter(|bytes| *bytes > 0) {
+            writeln!(out, "About {} is probably in what could not be read", human_bytes(hidden as f64))?;
+        }
+    }
+    Ok(())
+}
+
+fn write_rows(out: &mut impl Write, node: &FileInfo, parent_size: u64, level: usize) -> io::Result<()> {
+    let share = if parent_size > 0 { node.size as f64 * 100.0 / parent_size as f64 } else { 0.0 };
+    let name = if level == 0 { node.path.as_str() } else { node.name.as_str() };
+    let marker = if node.is_dir { "/" } else { "" };
+    writeln!(
+        out,
+        "{:>10} {:>5.1}% {:>9}  {}{}{}",
+        human_bytes(node.size as f64),
+        share,
+        node.file_count,
+        "  ".repeat(level),
+        name,
+        marker,
+    )?;
+    for child in &node.children {
+        write_rows(out, child, node.size, level + 1)?;
+    }
+    Ok(())
+}
+
+fn write_csv(out: &mut impl Write, result: &ScanResult) -> io::Result<()> {
+    writeln!(out, "depth,path,name,kind,size,apparent_size,allocated_size,file_count")?;
+    write_csv_rows(out, &result.root, 0)
+}
+
+fn write_csv_rows(out: &mut impl Write, node: &FileInfo, depth: usize) -> io::Result<()> {
+    writeln!(
+        out,
+        "{},{},{},{},{},{},{},{}",
+        depth,
+        csv_field(&node.path),
+        csv_field(&node.name),
+        node.kind.as_str(),
+        node.size,
+        node.apparent_size,
+        node.allocated_size,
+        node.file_count,
+    )?;
+    for child in &node.children {
+        write_csv_rows(out, child, depth + 1)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,355 @@
+//! Interactive terminal browser in the spirit of ncdu, for SSH sessions on machines without a
+//! desktop. It runs the same scan as the desktop app, deletes through the same checks and
+//! records deletions in the same audit log.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use clap::Parser;
+use disk_analyzer_lib::audit;
+use disk_analyzer_lib::delete::{delete_path, DeleteMode};
+use disk_analyzer_lib::protect::{ProtectedPaths, ProtectionReason};
+use disk_analyzer_lib::{
+    app_data_dir, find_node, replace_node, scan_tree, sort_children, FileInfo, ProgressSink,
+    ScanOptions, ScanProgress, SizeMode, SortKey, SymlinkPolicy,
+};
+use human_bytes::human_bytes;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Gauge, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+/// Width of the percentage bar in front of each entry
+const BAR_WIDTH: usize = 20;
+
+#[derive(Parser)]
+#[command(name = "disk-analyzer-tui", version, about = "Browse disk usage interactively")]
+struct Args {
+    /// Directory to scan
+    #[arg(default_value = ".")]
+    root: PathBuf,
+
+    /// Skip entries matching a glob; a pattern with a `/` is matched against the whole path
+    #[arg(short, long = "exclude", value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Report blocks allocated on disk instead of apparent sizes
+    #[arg(long)]
+    allocated: bool,
+
+    /// Don't cross into other filesystems
+    #[arg(short = 'x', long)]
+    one_file_system: bool,
+
+    /// Follow symbolic links
+    #[arg(short = 'L', long)]
+    follow_symlinks: bool,
+}
+
+impl Args {
+    fn scan_options(&self) -> ScanOptions {
+        ScanOptions {
+            size_mode: if self.allocated { SizeMode::Allocated } else { SizeMode::Apparent },
+            one_file_system: self.one_file_system,
+            symlinks: if self.follow_symlinks { SymlinkPolicy::Follow } else { SymlinkPolicy::DontFollow },
+            exclude: self.exclude.clone(),
+            ..ScanOptions::default()
+        }
+    }
+}
+
+/// Keeps the most recent progress for the UI thread to draw.
+#[derive(Default)]
+struct LatestProgress(Mutex<Option<ScanProgress>>);
+
+impl ProgressSink for LatestProgress {
+    fn progress(&self, progress: &ScanProgress) {
+        *self.0.lock().unwrap() = Some(progress.clone());
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let root = args.root.canonicalize().unwrap_or_else(|_| args.root.clone());
+    // The desktop app's list, user additions included; without it nothing could be deleted
+    // safely, so a broken list stops here
+    let data_dir = app_data_dir();
+    let loaded = data_dir.as_deref().map_or_else(|| Ok(ProtectedPaths::builtin()), ProtectedPaths::load);
+    let protected = match loaded {
+        Ok(protected) => protected,
+        Err(e) => {
+            eprintln!("disk-analyzer-tui: protected paths: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut terminal = ratatui::init();
+    let outcome = scan(&mut terminal, &root, &args.scan_options()).and_then(|tree| match tree {
+        Some(tree) => {
+            let audit_log = data_dir.map(|dir| dir.join(audit::LOG_FILE));
+            Browser::new(tree, protected, audit_log).run(&mut terminal)
+        }
+        None => Ok(()),
+    });
+    ratatui::restore();
+
+    match outcome {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("disk-analyzer-tui: {}: {}", root.display(), e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Scans on a worker thread while drawing progress. Returns `None` when the user quit early.
+fn scan(terminal: &mut DefaultTerminal, root: &Path, options: &ScanOptions) -> io::Result<Option<FileInfo>> {
+    let latest = LatestProgress::default();
+    let cancel_flag = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        let worker = scope.spawn(|| {
+            scan_tree(root, 0, options, None, &cancel_flag, &latest).map_err(|e| e.to_string())
+        });
+
+        while !worker.is_finished() {
+            terminal.draw(|frame| draw_progress(frame, root, latest.0.lock().unwrap().as_ref()))?;
+            if event::poll(Duration::from_millis(100))? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press && matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                        cancel_flag.store(true, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+
+        let result = worker.join().expect("scan thread panicked").map_err(io::Error::other)?;
+        Ok((!result.cancelled).then_some(result.root))
+    })
+}
+
+fn draw_progress(frame: &mut Frame, root: &Path, progress: Option<&ScanProgress>) {
+    let [status, gauge, current, _] = Layout::vertical([
+        Constraint::Length(2),
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Fill(1),
+    ])
+    .areas(frame.area());
+
+    let counts = progress
+        .map(|progress| {
+            format!(
+                "{} files, {} directories, {}",
+                progress.files_processed,
+                progress.directories_processed,
+                human_bytes(progress.total_size_so_far as f64),
+            )
+        })
+        .unwrap_or_default();
+    frame.render_widget(
+        Paragraph::new(vec![Line::from(format!("Scanning {} (q to stop)", root.display())), Line::from(counts)]),
+        status,
+    );
+
+    if let Some(percent) = progress.and_then(|progress| progress.percent_complete) {
+        frame.render_widget(Gauge::default().ratio((percent / 100.0).clamp(0.0, 1.0)), gauge);
+    }
+    if let Some(progress) = progress {
+        frame.render_widget(Paragraph::new(progress.current_path.as_str()), current);
+    }
+}
+
+/// Children of `dir` in the chosen order, or nothing if it has gone from the tree.
+fn entries<'a>(tree: &'a FileInfo, dir: &Path, sort: SortKey) -> Vec<&'a FileInfo> {
+    let mut children: Vec<&FileInfo> = find_node(tree, dir)
+        .map(|node| node.children.iter().collect())
+        .unwrap_or_default();
+    sort_children(&mut children, sort);
+    children
+}
+
+struct Browser {
+    tree: FileInfo,
+    /// Directory whose children are listed
+    current: PathBuf,
+    sort: SortKey,
+    list: ListState,
+    /// Entry waiting for the user to confirm its deletion
+    pending_delete: Option<(PathBuf, DeleteMode)>,
+    /// The desktop app's protected paths plus the scan root, checked before anything is deleted
+    protected: ProtectedPaths,
+    /// The desktop app's audit log, which deletions are recorded in
+    audit_log: Option<PathBuf>,
+    message: Option<String>,
+}
+
+impl Browser {
+    fn new(tree: FileInfo, mut protected: ProtectedPaths, audit_log: Option<PathBuf>) -> Self {
+        protected.add(&tree.path, ProtectionReason::ScanRoot);
+        Browser {
+            current: PathBuf::from(&tree.path),
+            tree,
+            sort: SortKey::Size,
+            list: ListState::default().with_selected(Some(0)),
+            pending_delete: None,
+            protected,
+            audit_log,
+            message: None,
+        }
+    }
+
+    fn run(mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if let Some((path, mode)) = self.pending_delete.take() {
+                if key.code == KeyCode::Char('y') {
+                    self.delete(&path, mode);
+                }
+                continue;
+            }
+
+            self.message = None;
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Up | KeyCode::Char('k') => self.list.select_previous(),
+                KeyCode::Down | KeyCode::Char('j') => self.list.select_next(),
+                KeyCode::Home => self.list.select_first(),
+                KeyCode::End => self.list.select_last(),
+                KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => self.open(),
+                KeyCode::Left | KeyCode::Backspace | KeyCode::Char('h') => self.leave(),
+                KeyCode::Char('s') => self.sort = SortKey::Size,
+                KeyCode::Char('c') => self.sort = SortKey::Count,
+                KeyCode::Char('m') => self.sort = SortKey::Mtime,
+                KeyCode::Char('n') => self.sort = SortKey::Name,
+                KeyCode::Char('d') => self.request_delete(DeleteMode::Trash),
+                KeyCode::Char('D') => self.request_delete(DeleteMode::Permanent),
+                _ => {}
+            }
+        }
+    }
+
+    fn selected(&self) -> Option<&FileInfo> {
+        let index = self.list.selected()?;
+        entries(&self.tree, &self.current, self.sort).get(index).copied()
+    }
+
+    fn open(&mut self) {
+        if let Some(dir) = self.selected().filter(|entry| entry.is_dir).map(|entry| PathBuf::from(&entry.path)) {
+            self.current = dir;
+            self.list.select(Some(0));
+        }
+    }
+
+    /// Goes up a level, keeping the directory we came from highlighted.
+    fn leave(&mut self) {
+        if self.current == Path::new(&self.tree.path) {
+            return;
+        }
+        let Some(parent) = self.current.parent().map(Path::to_path_buf) else {
+            return;
+        };
+        let index = entries(&self.tree, &parent, self.sort)
+            .iter()
+            .position(|entry| Path::new(&entry.path) == self.current);
+        self.current = parent;
+        self.list.select(Some(index.unwrap_or(0)));
+    }
+
+    fn request_delete(&mut self, mode: DeleteMode) {
+        self.pending_delete = self.selected().map(|entry| (PathBuf::from(&entry.path), mode));
+    }
+
+    fn delete(&mut self, path: &Path, mode: DeleteMode) {
+        let size = find_node(&self.tree, path).map_or(0, |node| node.size);
+        let result = delete_path(path, mode, &self.protected);
+        let entry = audit::deletion_entry(mode, &path.to_string_lossy(), size, result.as_ref());
+        let logged = match &self.audit_log {
+            Some(audit_log) => audit::append(audit_log, &[entry]).map_err(|e| e.to_string()),
+            None => Err("no data directory".to_string()),
+        };
+
+        match result {
+            Ok(outcome) => {
+                replace_node(&mut self.tree, path, None);
+                let action = match mode {
+                    DeleteMode::Trash => "Moved to trash",
+                    DeleteMode::Permanent => "Deleted",
+                };
+                self.message = Some(format!(
+                    "{} {} ({} freed)",
+                    action,
+                    path.display(),
+                    human_bytes(outcome.bytes_freed as f64)
+                ));
+            }
+            Err(e) => self.message = Some(format!("Not deleted: {}", e)),
+        }
+        if let (Err(e), Some(message)) = (logged, &mut self.message) {
+            message.push_str(&format!("; could not write the audit log: {}", e));
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, body, footer] =
+            Layout::vertical([Constraint::Length(1), Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+
+        let dir_size = find_node(&self.tree, &self.current).map_or(0, |node| node.size);
+        frame.render_widget(
+            Paragraph::new(format!(
+                "{}  {}  sorted by {:?}",
+                self.current.display(),
+                human_bytes(dir_size as f64),
+                self.sort
+            ))
+            .style(Style::new().add_modifier(Modifier::BOLD)),
+            header,
+        );
+
+        let items: Vec<ListItem> = entries(&self.tree, &self.current, self.sort)
+            .into_iter()
+            .map(|entry| ListItem::new(entry_line(entry, dir_size)))
+            .collect();
+        let list = List::new(items)
+            .block(Block::new().borders(Borders::TOP | Borders::BOTTOM))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, body, &mut self.list);
+
+        let status = match (&self.pending_delete, &self.message) {
+            (Some((path, DeleteMode::Trash)), _) => format!("Move {} to trash? (y/N)", path.display()),
+            (Some((path, DeleteMode::Permanent)), _) => format!("Delete {} permanently? (y/N)", path.display()),
+            (None, Some(message)) => message.clone(),
+            (None, None) => "↑↓ move  → open  ← up  s/c/m/n sort by size/count/mtime/name  d trash  D delete  q quit".to_string(),
+        };
+        frame.render_widget(Paragraph::new(status), footer);
+    }
+}
+
+fn entry_line(entry: &FileInfo, dir_size: u64) -> String {
+    let share = if dir_size > 0 { entry.size as f64 / dir_size as f64 } else { 0.0 };
+    let filled = ((share * BAR_WIDTH as f64).round() as usize).min(BAR_WIDTH);
+    let marker = if entry.is_dir { "/" } else { "" };
+    format!(
+        "{:>10} {:>5.1}% [{}{}] {:>9}  {}{}",
+        human_bytes(entry.size as f64),
+        share * 100.0,
+        "#".repeat(filled),
+        " ".repeat(BAR_WIDTH - filled),
+        entry.file_count,
+        entry.name,
+        marker,
+    )
+}
@@ -0,0 +1,547 @@
+//! Removing the files and folders a user picks in one of the views. The desktop app and the
+//! terminal UI both delete through here, so they apply the same checks.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::protect::{self, ProtectedPathError, ProtectedPaths};
+use crate::scanner::allocated_size;
+use crate::trash;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DeleteMode {
+    /// Move to the Trash, where it can be restored from
+    #[default]
+    Trash,
+    /// Remove for good
+    Permanent,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeleteOutcome {
+    pub path: String,
+    pub mode: DeleteMode,
+    /// Disk space the item took up where it was. Trashed items keep using it until the trash
+    /// is emptied
+    pub bytes_freed: u64,
+    /// Where a trashed item now lives
+    pub trash_path: Option<String>,
+}
+
+/// Why a deletion didn't happen. Serialized with a `kind` tag so the frontend can tell a
+/// refusal from a failure.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DeleteError {
+    Protected(ProtectedPathError),
+    NotFound { path: String },
+    Io { path: String, message: String },
+}
+
+impl DeleteError {
+    fn io(path: &Path, error: io::Error) -> Self {
+        DeleteError::Io {
+            path: path.to_string_lossy().to_string(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for DeleteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeleteError::Protected(error) => write!(f, "{}", error),
+            DeleteError::NotFound { path } => write!(f, "{} does not exist", path),
+            DeleteError::Io { path, message } => write!(f, "{}: {}", path, message),
+        }
+    }
+}
+
+impl std::error::Error for DeleteError {}
+
+impl From<ProtectedPathError> for DeleteError {
+    fn from(error: ProtectedPathError) -> Self {
+        DeleteError::Protected(error)
+    }
+}
+
+/// Deletes a file, or a directory with everything in it, either to the trash or for good.
+/// Anything in `protected`, or containing something in it, is refused.
+pub fn delete_path(path: &Path, mode: DeleteMode, protected: &ProtectedPaths) -> Result<DeleteOutcome, DeleteError> {
+    protected.check(path)?;
+    let metadata = fs::symlink_metadata(path).map_err(|_| DeleteError::NotFound {
+        path: path.to_string_lossy().to_string(),
+    })?;
+    let bytes_freed = disk_usage(path);
+
+    let removed = match mode {
+        DeleteMode::Trash => trash::move_to_trash(path).map(|item| Some(item.trashed.to_string_lossy().to_string())),
+        DeleteMode::Permanent if metadata.is_dir() => fs::remove_dir_all(path).map(|_| None),
+        DeleteMode::Permanent => fs::remove_file(path).map(|_| None),
+    };
+    let trash_path = removed.map_err(|e| DeleteError::io(path, e))?;
+
+    Ok(DeleteOutcome {
+        path: path.to_string_lossy().to_string(),
+        mode,
+        bytes_freed,
+        trash_path,
+    })
+}
+
+/// Bytes allocated to `path` and everything below it, without following symlinks.
+pub fn disk_usage(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .follow_links(false)
+        // A symlink given as the root would otherwise be walked into as well
+        .follow_root_links(false)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| allocated_size(&metadata))
+        .sum()
+}
+
+/// Problems reported per planned item; the rest are only counted.
+const MAX_PLAN_PROBLEMS: usize = 100;
+
+/// Something the pre-flight check expects to get in the way of removing an entry.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlanProblem {
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlannedItem {
+    pub path: String,
+    pub bytes: u64,
+    pub files: usize,
+    pub dirs: usize,
+    /// The first `MAX_PLAN_PROBLEMS` entries that look like they can't be removed
+    pub problems: Vec<PlanProblem>,
+    pub problem_count: usize,
+    /// Another selected, unprotected path that contains this one (or is the same); this item
+    /// is removed with it, or on its own should removing that one fail
+    pub covered_by: Option<String>,
+    /// Set when the item is protected; it will be skipped
+    pub protected: Option<ProtectedPathError>,
+}
+
+/// The pre-flight report for a batch deletion. Totals only count the items that will be
+/// carried out.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeletionPlan {
+    pub plan_id: u64,
+    pub mode: DeleteMode,
+    pub items: Vec<PlannedItem>,
+    pub total_bytes: u64,
+    pub total_files: usize,
+    pub total_dirs: usize,
+}
+
+/// Payload of the `deletion-progress` event, sent after each item of a plan.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeletionProgress {
+    pub plan_id: u64,
+    pub current_path: String,
+    pub completed_items: usize,
+    pub total_items: usize,
+    pub bytes_done: u64,
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ItemResult {
+    Deleted { outcome: DeleteOutcome },
+    Failed { path: String, error: DeleteError },
+    Skipped { path: String, reason: String },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeletionReport {
+    pub plan_id: u64,
+    pub results: Vec<ItemResult>,
+    pub deleted: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub bytes_freed: u64,
+    /// Set when the audit log couldn't be written; the deletions happened all the same
+    pub audit_error: Option<String>,
+}
+
+/// Works out what deleting `paths` would involve without touching anything: sizes, entries
+/// that look undeletable, selections nested in other selections and protected paths.
+pub fn plan_deletion(paths: &[String], mode: DeleteMode, protected: &ProtectedPaths) -> DeletionPlan {
+    let resolved: Vec<PathBuf> = paths
+        .iter()
+        .map(|path| protect::resolve(Path::new(path)).unwrap_or_else(|_| PathBuf::from(path)))
+        .collect();
+
+    let refusals: Vec<Option<ProtectedPathError>> =
+        paths.iter().map(|path| protected.check(Path::new(path)).err()).collect();
+
+    let items: Vec<PlannedItem> = paths
+        .iter()
+        .zip(refusals.iter().cloned())
+        .enumerate()
+        .map(|(index, (path, protected))| {
+            // The outermost selection containing this one covers it, so no cover is covered
+            // itself. Of two identical selections the first one is kept, and a protected
+            // selection covers nothing since it is never removed
+            let covered_by = resolved
+                .iter()
+                .enumerate()
+                .filter(|&(other, outer)| {
+                    other != index
+                        && refusals[other].is_none()
+                        && resolved[index].starts_with(outer)
+                        && (outer != &resolved[index] || other < index)
+                })
+                .min_by_key(|&(other, outer)| (outer.components().count(), other))
+                .map(|(other, _)| paths[other].clone());
+            let mut item = survey(Path::new(path), mode);
+            item.covered_by = covered_by;
+            item.protected = protected;
+            item
+        })
+        .collect();
+
+    let carried_out = items.iter().filter(|item| item.covered_by.is_none() && item.protected.is_none());
+    let (total_bytes, total_files, total_dirs) = carried_out.fold((0, 0, 0), |(bytes, files, dirs), item| {
+        (bytes + item.bytes, files + item.files, dirs + item.dirs)
+    });
+
+    DeletionPlan {
+        plan_id: 0,
+        mode,
+        items,
+        total_bytes,
+        total_files,
+        total_dirs,
+    }
+}
+
+impl PlannedItem {
+    fn problem(&mut self, path: &Path, message: String) {
+        self.problem_count += 1;
+        if self.problems.len() < MAX_PLAN_PROBLEMS {
+            self.problems.push(PlanProblem {
+                path: path.to_string_lossy().to_string(),
+                message,
+            });
+        }
+    }
+}
+
+/// Sizes one selected path and looks for permission problems on the way.
+fn survey(path: &Path, mode: DeleteMode) -> PlannedItem {
+    let mut item = PlannedItem {
+        path: path.to_string_lossy().to_string(),
+        bytes: 0,
+        files: 0,
+        dirs: 0,
+        problems: Vec::new(),
+        problem_count: 0,
+        covered_by: None,
+        protected: None,
+    };
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            item.problem(path, e.to_string());
+            return item;
+        }
+    };
+
+    // Removing or renaming an entry needs write access to the directory holding it, and
+    // moving a directory elsewhere also rewrites its `..`
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        if !has_access(parent, Access::Modify) {
+            item.problem(parent, "No permission to remove entries from this directory".to_string());
+        }
+    }
+    if mode == DeleteMode::Trash && metadata.is_dir() && !has_access(path, Access::Modify) {
+        item.problem(path, "No permission to move this directory".to_string());
+    }
+
+    // Not following the root either: removing a link to a directory only removes the link
+    for entry in WalkDir::new(path).follow_links(false).follow_root_links(false) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                let at = e.path().unwrap_or(path).to_path_buf();
+                item.problem(&at, e.to_string());
+                continue;
+            }
+        };
+        if let Ok(metadata) = entry.metadata() {
+            item.bytes += allocated_size(&metadata);
+        }
+        if entry.file_type().is_dir() {
+            item.dirs += 1;
+            // Only a permanent delete empties directories one entry at a time
+            if mode == DeleteMode::Permanent && !has_access(entry.path(), Access::Empty) {
+                item.problem(entry.path(), "No permission to remove this directory's contents".to_string());
+            }
+        } else {
+            item.files += 1;
+        }
+    }
+    item
+}
+
+/// Carries out `plan` one item at a time, skipping protected selections and nested ones whose
+/// cover was removed. Covered items come last: if their cover couldn't be removed, they are
+/// tried on their own. Every item is checked against `protected` again, since things may
+/// have changed since planning. `on_item` hears about each item as soon as it is done with,
+/// along with the progress so far; `results` keep the plan's order.
+pub fn execute_plan(
+    plan: &DeletionPlan,
+    protected: &ProtectedPaths,
+    on_item: &mut dyn FnMut(&DeletionProgress, &PlannedItem, &ItemResult),
+) -> DeletionReport {
+    let mut report = DeletionReport {
+        plan_id: plan.plan_id,
+        results: Vec::with_capacity(plan.items.len()),
+        deleted: 0,
+        failed: 0,
+        skipped: 0,
+        bytes_freed: 0,
+        audit_error: None,
+    };
+    let mut bytes_done = 0;
+    let mut results: Vec<Option<ItemResult>> = plan.items.iter().map(|_| None).collect();
+
+    let uncovered = (0..plan.items.len()).filter(|&index| plan.items[index].covered_by.is_none());
+    let covered = (0..plan.items.len()).filter(|&index| plan.items[index].covered_by.is_some());
+    for (done, index) in uncovered.chain(covered).enumerate() {
+        let item = &plan.items[index];
+        let cover_removed = item.covered_by.as_ref().is_some_and(|outer| {
+            plan.items.iter().zip(&results).any(|(other, result)| {
+                other.covered_by.is_none() && &other.path == outer && matches!(result, Some(ItemResult::Deleted { .. }))
+            })
+        });
+        let skip_reason = match (&item.covered_by, &item.protected) {
+            (Some(outer), _) if cover_removed => Some(format!("Removed along with {}", outer)),
+            (_, Some(protected)) => Some(protected.to_string()),
+            _ => None,
+        };
+        let result = match skip_reason {
+            Some(reason) => {
+                report.skipped += 1;
+                ItemResult::Skipped { path: item.path.clone(), reason }
+            }
+            None => match delete_path(Path::new(&item.path), plan.mode, protected) {
+                Ok(outcome) => {
+                    report.deleted += 1;
+                    report.bytes_freed += outcome.bytes_freed;
+                    bytes_done += item.bytes;
+                    ItemResult::Deleted { outcome }
+                }
+                Err(error) => {
+                    report.failed += 1;
+                    ItemResult::Failed { path: item.path.clone(), error }
+                }
+            },
+        };
+
+        let progress = DeletionProgress {
+            plan_id: plan.plan_id,
+            current_path: item.path.clone(),
+            completed_items: done + 1,
+            total_items: plan.items.len(),
+            bytes_done,
+            total_bytes: plan.total_bytes,
+        };
+        on_item(&progress, item, &result);
+        results[index] = Some(result);
+    }
+    report.results = results.into_iter().flatten().collect();
+    report
+}
+
+#[derive(Clone, Copy)]
+enum Access {
+    /// Add or remove entries
+    Modify,
+    /// List and remove every entry
+    Empty,
+}
+
+#[cfg(unix)]
+fn has_access(path: &Path, access: Access) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let mode = match access {
+        Access::Modify => libc::W_OK | libc::X_OK,
+        Access::Empty => libc::R_OK | libc::W_OK | libc::X_OK,
+    };
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return true;
+    };
+    // SAFETY: c_path is NUL-terminated and access only reads it
+    unsafe { libc::access(c_path.as_ptr(), mode) == 0 }
+}
+
+/// Without a cheap way to ask, assume access and let the deletion report any failure.
+#[cfg(not(unix))]
+fn has_access(_path: &Path, _access: Access) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit;
+    use crate::protect::ProtectionReason;
+    use tempfile::TempDir;
+
+    /// A scratch directory holding `dir/sub/file` and `other`, removed when the `TempDir` is
+    /// dropped. The path is resolved, as `plan_deletion` reports resolved covers.
+    fn scratch_tree() -> (TempDir, PathBuf) {
+        let scratch = TempDir::new().unwrap();
+        let root = fs::canonicalize(scratch.path()).unwrap();
+        fs::create_dir_all(root.join("dir").join("sub")).unwrap();
+        fs::write(root.join("dir").join("sub").join("file"), b"contents").unwrap();
+        fs::write(root.join("other"), b"more contents").unwrap();
+        (scratch, root)
+    }
+
+    fn plan(paths: &[PathBuf]) -> DeletionPlan {
+        let paths: Vec<String> = paths.iter().map(|path| path.to_string_lossy().to_string()).collect();
+        plan_deletion(&paths, DeleteMode::Permanent, &ProtectedPaths::default())
+    }
+
+    fn covered_by(plan: &DeletionPlan) -> Vec<Option<&str>> {
+        plan.items.iter().map(|item| item.covered_by.as_deref()).collect()
+    }
+
+    #[test]
+    fn nested_selections_are_covered_by_the_outer_one_in_either_order() {
+        let (_scratch, root) = scratch_tree();
+        let dir = root.join("dir");
+        let file = dir.join("sub").join("file");
+        let dir_str = dir.to_string_lossy();
+
+        let outer_first = plan(&[dir.clone(), file.clone()]);
+        assert_eq!(covered_by(&outer_first), vec![None, Some(dir_str.as_ref())]);
+        let inner_first = plan(&[file.clone(), dir.clone()]);
+        assert_eq!(covered_by(&inner_first), vec![Some(dir_str.as_ref()), None]);
+
+        // Totals count the outer selection only
+        let alone = plan(std::slice::from_ref(&dir));
+        for nested in [&outer_first, &inner_first] {
+            assert_eq!(
+                (nested.total_bytes, nested.total_files, nested.total_dirs),
+                (alone.total_bytes, alone.total_files, alone.total_dirs)
+            );
+        }
+        assert_eq!((alone.total_files, alone.total_dirs), (1, 2));
+    }
+
+    #[test]
+    fn duplicate_selections_keep_the_first() {
+        let (_scratch, root) = scratch_tree();
+        let dir = root.join("dir");
+        let dir_str = dir.to_string_lossy();
+        let respelled = root.join("dir").join("sub").join("..").join("..").join("dir");
+
+        let twice = plan(&[dir.clone(), dir.clone()]);
+        assert_eq!(covered_by(&twice), vec![None, Some(dir_str.as_ref())]);
+        assert_eq!(twice.total_files, 1);
+
+        let other_spelling = plan(&[dir.clone(), respelled]);
+        assert_eq!(covered_by(&other_spelling), vec![None, Some(dir_str.as_ref())]);
+    }
+
+    #[test]
+    fn protected_selections_cover_nothing() {
+        let (_scratch, root) = scratch_tree();
+        let dir = root.join("dir");
+        let mut protected = ProtectedPaths::default();
+        protected.add(&root, ProtectionReason::ScanRoot);
+
+        let paths: Vec<String> = [&root, &dir, &root.join("other")]
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        let plan = plan_deletion(&paths, DeleteMode::Permanent, &protected);
+        assert_eq!(covered_by(&plan), vec![None, None, None]);
+        assert!(plan.items[0].protected.is_some());
+        assert_eq!(plan.total_files, 2);
+
+        let report = execute_plan(&plan, &protected, &mut |_, _, _| {});
+        assert_eq!((report.deleted, report.skipped, report.failed), (2, 1, 0));
+        assert!(!dir.exists() && !root.join("other").exists());
+        assert!(matches!(report.results[0], ItemResult::Skipped { .. }));
+    }
+
+    #[test]
+    fn covered_items_are_removed_on_their_own_when_their_cover_fails() {
+        let (_scratch, root) = scratch_tree();
+        let dir = root.join("dir");
+        let file = dir.join("sub").join("file");
+        let plan = plan(&[file.clone(), dir.clone()]);
+        assert!(plan.items[0].covered_by.is_some());
+
+        // Protected by the time the plan runs, so the outer selection fails
+        let mut protected = ProtectedPaths::default();
+        protected.add(&dir, ProtectionReason::User);
+        let mut logged = Vec::new();
+        let report = execute_plan(&plan, &protected, &mut |_, item, result| {
+            logged.extend(audit::plan_item_entry(plan.mode, item, result));
+        });
+        assert!(matches!(report.results[0], ItemResult::Deleted { .. }));
+        assert!(matches!(report.results[1], ItemResult::Failed { .. }));
+        assert_eq!((report.deleted, report.failed), (1, 1));
+        assert!(!file.exists() && dir.exists());
+        assert_eq!(logged.len(), 2);
+    }
+
+    #[test]
+    fn covered_items_are_reported_removed_with_their_cover() {
+        let (_scratch, root) = scratch_tree();
+        let dir = root.join("dir");
+        let plan = plan(&[dir.join("sub"), dir.clone()]);
+
+        let mut logged = Vec::new();
+        let report = execute_plan(&plan, &ProtectedPaths::default(), &mut |_, item, result| {
+            logged.extend(audit::plan_item_entry(plan.mode, item, result));
+        });
+        assert!(matches!(report.results[0], ItemResult::Skipped { .. }));
+        assert!(matches!(report.results[1], ItemResult::Deleted { .. }));
+        assert_eq!((report.deleted, report.skipped), (1, 1));
+        assert_eq!(logged.len(), 1);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn siblings_sharing_a_name_prefix_are_not_nested() {
+        let (_scratch, root) = scratch_tree();
+        fs::write(root.join("dir-backup"), b"x").unwrap();
+
+        let siblings = plan(&[root.join("dir"), root.join("dir-backup"), root.join("other")]);
+        assert_eq!(covered_by(&siblings), vec![None, None, None]);
+        assert_eq!(siblings.total_files, 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_selections_count_only_the_link() {
+        let (_scratch, root) = scratch_tree();
+        let link = root.join("link");
+        std::os::unix::fs::symlink(root.join("dir"), &link).unwrap();
+
+        let linked = plan(&[link]);
+        assert_eq!((linked.total_files, linked.total_dirs), (1, 0));
+    }
+}
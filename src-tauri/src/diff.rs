@@ -0,0 +1,166 @@
+//! Differences between two scan trees, directory by directory.
+//!
+//! Each `DiffNode` also carries `size` = |delta| next to the usual `name`/`path`/`is_dir`/
+//! `children`, so the treemap and sunburst views can render growth as they render usage.
+//!
+//! Added and removed directories are kept as single nodes with their totals rather than
+//! listed entry by entry, since a new `node_modules` alone can hold hundreds of thousands.
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::scanner::FileInfo;
+use crate::snapshot::SnapshotMeta;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Grown,
+    Shrunk,
+    /// Same size on both sides; only kept when something underneath changed
+    Unchanged,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiffNode {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub change: ChangeKind,
+    pub old_size: u64,
+    pub new_size: u64,
+    /// `new_size - old_size`
+    pub delta: i64,
+    /// Absolute change, which is what charts size their boxes by
+    pub size: u64,
+    pub old_file_count: usize,
+    pub new_file_count: usize,
+    /// Number of changed children, which stays accurate when `children` is left out of a
+    /// view. For an added or removed directory, the entries it holds, which aren't listed
+    pub child_count: usize,
+    /// Changed children only, largest absolute change first
+    pub children: Vec<DiffNode>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotDiff {
+    /// Handle for paging through deeper levels of the diff
+    pub diff_id: u64,
+    pub from: SnapshotMeta,
+    pub to: SnapshotMeta,
+    pub root: DiffNode,
+}
+
+/// Compares two trees, matching children by name at every level.
+pub fn diff_trees(old: &FileInfo, new: &FileInfo) -> DiffNode {
+    diff_node(Some(old), Some(new))
+}
+
+fn diff_node(old: Option<&FileInfo>, new: Option<&FileInfo>) -> DiffNode {
+    // At least one side is always present
+    let either = new.or(old).expect("diff_node called without either side");
+    let old_size = old.map_or(0, |node| node.size);
+    let new_size = new.map_or(0, |node| node.size);
+    let delta = new_size as i64 - old_size as i64;
+
+    let change = match (old, new) {
+        (None, _) => ChangeKind::Added,
+        (_, None) => ChangeKind::Removed,
+        _ if delta > 0 => ChangeKind::Grown,
+        _ if delta < 0 => ChangeKind::Shrunk,
+        _ => ChangeKind::Unchanged,
+    };
+
+    if matches!(change, ChangeKind::Added | ChangeKind::Removed) {
+        return DiffNode {
+            name: either.name.clone(),
+            path: either.path.clone(),
+            is_dir: either.is_dir,
+            change,
+            old_size,
+            new_size,
+            delta,
+            size: delta.unsigned_abs(),
+            old_file_count: old.map_or(0, |node| node.file_count),
+            new_file_count: new.map_or(0, |node| node.file_count),
+            child_count: either.child_count,
+            children: Vec::new(),
+        };
+    }
+
+    let old_children: HashMap<&str, &FileInfo> = old
+        .map(|node| {
+            node.children
+                .iter()
+                .map(|child| (child.name.as_str(), child))
+                .collect()
+        })
+        .unwrap_or_default();
+    let new_children: &[FileInfo] = new.map_or(&[][..], |node| node.children.as_slice());
+
+    let mut children: Vec<DiffNode> = new_children
+        .iter()
+        .map(|child| diff_node(old_children.get(child.name.as_str()).copied(), Some(child)))
+        .collect();
+
+    if let Some(old) = old {
+        let new_names: HashSet<&str> = new_children
+            .iter()
+            .map(|child| child.name.as_str())
+            .collect();
+        children.extend(
+            old.children
+                .iter()
+                .filter(|child| !new_names.contains(child.name.as_str()))
+                .map(|child| diff_node(Some(child), None)),
+        );
+    }
+
+    children.retain(|child| child.change != ChangeKind::Unchanged || !child.children.is_empty());
+    children.sort_by_key(|child| Reverse(child.size));
+
+    DiffNode {
+        name: either.name.clone(),
+        path: either.path.clone(),
+        is_dir: old.is_some_and(|node| node.is_dir) || new.is_some_and(|node| node.is_dir),
+        change,
+        old_size,
+        new_size,
+        delta,
+        size: delta.unsigned_abs(),
+        old_file_count: old.map_or(0, |node| node.file_count),
+        new_file_count: new.map_or(0, |node| node.file_count),
+        child_count: children.len(),
+        children,
+    }
+}
+
+/// Copies `depth` levels of a diff for the frontend; deeper levels come from paging.
+pub fn diff_view(node: &DiffNode, depth: usize) -> DiffNode {
+    DiffNode {
+        children: if depth == 0 {
+            Vec::new()
+        } else {
+            node.children.iter().map(|child| diff_view(child, depth - 1)).collect()
+        },
+        name: node.name.clone(),
+        path: node.path.clone(),
+        ..*node
+    }
+}
+
+/// Walks from the diff's root down to `path`, one component at a time.
+pub fn find_diff_node<'a>(root: &'a DiffNode, path: &Path) -> Option<&'a DiffNode> {
+    let relative = path.strip_prefix(&root.path).ok()?;
+    let mut node = root;
+    for component in relative.components() {
+        let name = component.as_os_str().to_string_lossy();
+        node = node.children.iter().find(|child| child.name == name)?;
+    }
+    Some(node)
+}
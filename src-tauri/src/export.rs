@@ -0,0 +1,165 @@
+//! Writing scans out for other tools: the full tree as JSON, a flat CSV, or ncdu's export
+//! format so a scan can be opened with `ncdu -f`.
+//!
+//! Every format covers the whole stored tree, including what the views folded away, and is
+//! written node by node through a buffered writer, so even very large trees are never held
+//! in memory a second time.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::scanner::{FileInfo, NodeKind, ScanResult};
+
+/// Version of ncdu's export format that `Ncdu` writes
+pub(crate) const NCDU_MAJOR_VERSION: u32 = 1;
+const NCDU_MINOR_VERSION: u32 = 2;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// The `ScanResult` with its complete tree, as the frontend would receive it
+    Json,
+    /// One row per file and directory
+    Csv,
+    /// ncdu's JSON export format
+    Ncdu,
+}
+
+/// Writes `result` to `dest`. Like snapshots, the file only appears under its final name
+/// once it is complete.
+pub fn export(result: &ScanResult, format: ExportFormat, dest: &Path) -> io::Result<()> {
+    let mut temp_name = dest.as_os_str().to_owned();
+    temp_name.push(".tmp");
+    let temp_path = Path::new(&temp_name);
+
+    let mut writer = BufWriter::new(File::create(temp_path)?);
+    let written = match format {
+        ExportFormat::Json => serde_json::to_writer(&mut writer, result).map_err(io::Error::from),
+        ExportFormat::Csv => write_csv(&mut writer, &result.root),
+        ExportFormat::Ncdu => write_ncdu(&mut writer, &result.root),
+    };
+    if let Err(e) = written.and_then(|_| writer.flush()) {
+        let _ = fs::remove_file(temp_path);
+        return Err(e);
+    }
+    drop(writer);
+
+    fs::rename(temp_path, dest)
+}
+
+fn write_csv(out: &mut impl Write, root: &FileInfo) -> io::Result<()> {
+    writeln!(out, "path,size,allocated,count,mtime,kind")?;
+    write_csv_rows(out, root)
+}
+
+fn write_csv_rows(out: &mut impl Write, node: &FileInfo) -> io::Result<()> {
+    writeln!(
+        out,
+        "{},{},{},{},{},{}",
+        csv_field(&node.path),
+        node.size,
+        node.allocated_size,
+        node.file_count,
+        node.mtime.map(|ns| (ns / 1_000_000_000).to_string()).unwrap_or_default(),
+        node.kind.as_str(),
+    )?;
+    for child in &node.children {
+        write_csv_rows(out, child)?;
+    }
+    Ok(())
+}
+
+/// Quotes a CSV field when it holds a separator, quote or line break.
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Header block of an ncdu export.
+#[derive(Serialize)]
+struct NcduMeta {
+    progname: &'static str,
+    progver: &'static str,
+    timestamp: u64,
+}
+
+/// Info block of one entry. Directories are written as an array starting with theirs,
+/// followed by their children. A directory's sizes are those of the directory entry itself,
+/// which the tree doesn't keep, so they are written as zero; ncdu adds up the children.
+#[derive(Serialize)]
+struct NcduEntry<'a> {
+    name: &'a str,
+    asize: u64,
+    dsize: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mtime: Option<u64>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    notreg: bool,
+    /// ncdu's reason for not descending into a directory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    excluded: Option<&'static str>,
+}
+
+fn write_ncdu(out: &mut impl Write, root: &FileInfo) -> io::Result<()> {
+    let meta = NcduMeta {
+        progname: env!("CARGO_PKG_NAME"),
+        progver: env!("CARGO_PKG_VERSION"),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    };
+    write!(out, "[{},{},", NCDU_MAJOR_VERSION, NCDU_MINOR_VERSION)?;
+    serde_json::to_writer(&mut *out, &meta)?;
+    writeln!(out, ",")?;
+    write_ncdu_node(out, root, true)?;
+    writeln!(out, "]")
+}
+
+fn write_ncdu_node(out: &mut impl Write, node: &FileInfo, is_root: bool) -> io::Result<()> {
+    let descends = node.is_dir && node.kind != NodeKind::MountPoint;
+    let entry = NcduEntry {
+        name: ncdu_name(node, is_root),
+        asize: if descends { 0 } else { node.apparent_size },
+        dsize: if descends { 0 } else { node.allocated_size },
+        mtime: node.mtime.and_then(|ns| u64::try_from(ns / 1_000_000_000).ok()),
+        notreg: node.kind == NodeKind::Symlink && !node.is_dir,
+        excluded: (node.kind == NodeKind::MountPoint).then_some("otherfs"),
+    };
+
+    // A mount point the scan stopped at has no children; ncdu lists those as plain entries
+    if !descends {
+        return serde_json::to_writer(&mut *out, &entry).map_err(io::Error::from);
+    }
+
+    write!(out, "[")?;
+    serde_json::to_writer(&mut *out, &entry)?;
+    for child in &node.children {
+        writeln!(out, ",")?;
+        write_ncdu_node(out, child, false)?;
+    }
+    write!(out, "]")
+}
+
+/// ncdu names the root by its full path and every other entry by its own name. This and
+/// `ncdu_path` keep the exporter and the importer agreeing on that.
+fn ncdu_name(node: &FileInfo, is_root: bool) -> &str {
+    if is_root {
+        &node.path
+    } else {
+        &node.name
+    }
+}
+
+/// The path of the ncdu entry called `name` below `parent`, or of the root when there is no
+/// parent; see `ncdu_name`.
+pub(crate) fn ncdu_path(parent: Option<&Path>, name: &str) -> PathBuf {
+    parent.map_or_else(|| PathBuf::from(name), |parent| parent.join(name))
+}
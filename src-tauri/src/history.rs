@@ -0,0 +1,266 @@
+//! Recent file operations, so they can be undone. Only moves to the trash are recorded: a
+//! permanent deletion can't be taken back.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::trash;
+
+/// Oldest operations are forgotten past this many.
+const MAX_OPERATIONS: usize = 50;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationKind {
+    Trash,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrashedEntry {
+    pub original: String,
+    pub trash_path: String,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Operation {
+    pub id: u64,
+    pub kind: OperationKind,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub items: Vec<TrashedEntry>,
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    next_id: u64,
+    operations: Vec<Operation>,
+}
+
+impl History {
+    /// Records an operation, unless it didn't affect anything.
+    pub fn record(&mut self, kind: OperationKind, items: Vec<TrashedEntry>) -> Option<u64> {
+        if items.is_empty() {
+            return None;
+        }
+        self.next_id += 1;
+        self.operations.push(Operation {
+            id: self.next_id,
+            kind,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            items,
+        });
+        if self.operations.len() > MAX_OPERATIONS {
+            self.operations.remove(0);
+        }
+        Some(self.next_id)
+    }
+
+    /// Newest first.
+    pub fn list(&self) -> Vec<Operation> {
+        self.operations.iter().rev().cloned().collect()
+    }
+
+    pub fn pop_last(&mut self) -> Option<Operation> {
+        self.operations.pop()
+    }
+
+    /// Puts back what an undo left behind, as the newest operation again.
+    pub fn push_back(&mut self, operation: Operation) {
+        self.operations.push(operation);
+    }
+}
+
+/// An item whose original location is taken again.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UndoConflict {
+    pub path: String,
+    /// Free name next to it that the item can be restored under instead
+    pub suggested_path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RestoredItem {
+    pub original: String,
+    /// Differs from `original` when a conflict was resolved by renaming
+    pub restored_to: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UndoFailure {
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UndoReport {
+    pub operation_id: u64,
+    pub restored: Vec<RestoredItem>,
+    /// Conflicts nothing was done about; call again with `rename_conflicts` to restore these
+    /// items under their suggested names
+    pub conflicts: Vec<UndoConflict>,
+    /// Items that couldn't be restored. Those no longer in the trash are dropped from the
+    /// history; the others stay for another try
+    pub failed: Vec<UndoFailure>,
+    /// Set when the audit log couldn't be written; the items were restored all the same
+    pub audit_error: Option<String>,
+}
+
+/// Restores what `operation` moved to the trash. When any original location is taken again,
+/// nothing is restored and the conflicts are reported, unless `rename_conflicts` allows
+/// restoring those items under a free name next to the original.
+///
+/// Returns what is left to undo, if anything, so it can go back into the history. Items
+/// emptied from the trash since can never come back, so they are reported and left out.
+pub fn undo(operation: Operation, rename_conflicts: bool) -> (UndoReport, Option<Operation>) {
+    let mut report = UndoReport {
+        operation_id: operation.id,
+        restored: Vec::new(),
+        conflicts: Vec::new(),
+        failed: Vec::new(),
+        audit_error: None,
+    };
+
+    let (items, gone): (Vec<_>, Vec<_>) = operation
+        .items
+        .into_iter()
+        .partition(|item| !is_not_found(fs::symlink_metadata(&item.trash_path)));
+    report.failed = gone
+        .into_iter()
+        .map(|item| UndoFailure {
+            path: item.original,
+            message: format!("{} is no longer in the trash", item.trash_path),
+        })
+        .collect();
+    if items.is_empty() {
+        return (report, None);
+    }
+    let operation = Operation { items, ..operation };
+
+    report.conflicts = operation
+        .items
+        .iter()
+        .filter(|item| fs::symlink_metadata(&item.original).is_ok())
+        .map(|item| UndoConflict {
+            path: item.original.clone(),
+            suggested_path: free_name(Path::new(&item.original)).to_string_lossy().to_string(),
+        })
+        .collect();
+    if !report.conflicts.is_empty() && !rename_conflicts {
+        return (report, Some(operation));
+    }
+
+    // Dealt with by restoring under free names; anything taken from here on is a new conflict
+    report.conflicts.clear();
+    let mut remaining = Vec::new();
+    for item in operation.items {
+        let original = Path::new(&item.original);
+        let dest = match fs::symlink_metadata(original) {
+            Ok(_) => free_name(original),
+            Err(_) => original.to_path_buf(),
+        };
+        match trash::restore(Path::new(&item.trash_path), &dest) {
+            Ok(()) => report.restored.push(RestoredItem {
+                original: item.original.clone(),
+                restored_to: dest.to_string_lossy().to_string(),
+            }),
+            // Taken since the check above
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                report.conflicts.push(UndoConflict {
+                    path: item.original.clone(),
+                    suggested_path: free_name(original).to_string_lossy().to_string(),
+                });
+                remaining.push(item);
+            }
+            Err(e) => {
+                let gone = e.kind() == io::ErrorKind::NotFound;
+                report.failed.push(UndoFailure {
+                    path: item.original.clone(),
+                    message: e.to_string(),
+                });
+                if !gone {
+                    remaining.push(item);
+                }
+            }
+        }
+    }
+
+    let remaining = (!remaining.is_empty()).then_some(Operation { items: remaining, ..operation });
+    (report, remaining)
+}
+
+fn is_not_found<T>(result: io::Result<T>) -> bool {
+    matches!(result, Err(e) if e.kind() == io::ErrorKind::NotFound)
+}
+
+/// `name (restored).ext`, then `name (restored 2).ext` and so on, whichever is free first.
+fn free_name(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1..)
+        .map(|attempt| {
+            let suffix = if attempt == 1 { "restored".to_string() } else { format!("restored {}", attempt) };
+            path.with_file_name(format!("{} ({}){}", stem, suffix, extension))
+        })
+        .find(|candidate| fs::symlink_metadata(candidate).is_err())
+        .expect("some numbered name is free")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn operation(root: &Path, names: &[&str]) -> Operation {
+        Operation {
+            id: 1,
+            kind: OperationKind::Trash,
+            timestamp: 0,
+            items: names
+                .iter()
+                .map(|name| TrashedEntry {
+                    original: root.join(name).to_string_lossy().to_string(),
+                    trash_path: root.join("trash").join(name).to_string_lossy().to_string(),
+                    bytes: 1,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn items_emptied_from_the_trash_are_reported_and_dropped() {
+        let scratch = TempDir::new().unwrap();
+        let root = scratch.path();
+        fs::create_dir_all(root.join("trash")).unwrap();
+        fs::write(root.join("trash").join("kept"), b"x").unwrap();
+
+        let (report, remaining) = undo(operation(root, &["kept", "emptied"]), false);
+        assert_eq!(report.restored.len(), 1);
+        assert!(root.join("kept").exists());
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].path, root.join("emptied").to_string_lossy());
+        assert!(remaining.is_none());
+
+        // Nothing left in the trash at all: nothing to put back into the history either
+        let (report, remaining) = undo(operation(root, &["emptied"]), false);
+        assert_eq!(report.failed.len(), 1);
+        assert!(remaining.is_none());
+
+        // An emptied item whose original location is taken again isn't a conflict
+        fs::write(root.join("emptied"), b"x").unwrap();
+        let (report, remaining) = undo(operation(root, &["emptied"]), false);
+        assert!(report.conflicts.is_empty());
+        assert!(remaining.is_none());
+    }
+}
@@ -0,0 +1,474 @@
+//! Reading scans made elsewhere: `ncdu -o` exports and `du -ab` listings, typically taken on
+//! servers where the app can't run. Both become an ordinary `ScanResult`, so they can be
+//! browsed, saved as snapshots and diffed like any scan.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{self, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::export;
+use crate::scanner::{
+    directory_node, FileInfo, NodeKind, ScanError, ScanErrorGroup, ScanErrorKind, ScanResult,
+    ScanTiming, SizeMode, MAX_REPORTED_ERRORS,
+};
+
+/// Reads the dump at `path`, telling the formats apart by their first character: ncdu
+/// exports are a JSON array, `du` lines start with a size.
+///
+/// `size_mode` picks which of ncdu's two sizes is used. `du -ab` only has apparent sizes, so
+/// they stand in for the allocated ones and the result is always in apparent mode.
+pub fn import(path: &Path, size_mode: SizeMode) -> io::Result<ScanResult> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let first = reader
+        .fill_buf()?
+        .iter()
+        .copied()
+        .find(|byte| !byte.is_ascii_whitespace());
+
+    let mut totals = Totals::default();
+    let (root, size_mode) = match first {
+        Some(b'[') => (read_ncdu(reader, size_mode, &mut totals)?, size_mode),
+        Some(_) => (read_du(reader, &mut totals)?, SizeMode::Apparent),
+        None => return Err(invalid_data("The file is empty")),
+    };
+
+    let error_count = totals.error_count;
+    Ok(ScanResult {
+        scan_id: 0,
+        size_mode,
+        total_size: root.size,
+        file_count: root.file_count,
+        directory_count: totals.directories,
+        reused_directories: 0,
+        rescanned_directories: 0,
+        error_count,
+        errors: totals.errors,
+        error_groups: if error_count > 0 {
+            vec![ScanErrorGroup { kind: ScanErrorKind::Io, count: error_count }]
+        } else {
+            Vec::new()
+        },
+        unreadable_bytes_estimate: None,
+        hard_link_duplicates: totals.hard_link_duplicates,
+        excluded_entries: totals.excluded_entries,
+        excluded_bytes: 0,
+        cancelled: false,
+        timing: ScanTiming::default(),
+        options: None,
+        root,
+    })
+}
+
+/// Counters for the `ScanResult` fields that aren't part of the tree.
+#[derive(Default)]
+struct Totals {
+    directories: usize,
+    hard_link_duplicates: usize,
+    excluded_entries: usize,
+    error_count: usize,
+    errors: Vec<ScanError>,
+}
+
+impl Totals {
+    fn record_error(&mut self, path: &str) {
+        self.error_count += 1;
+        if self.errors.len() < MAX_REPORTED_ERRORS {
+            self.errors.push(ScanError {
+                path: path.to_string(),
+                kind: ScanErrorKind::Io,
+                message: "Could not be read when the dump was made".to_string(),
+            });
+        }
+    }
+}
+
+/// Info block of one ncdu entry. Fields this app has no use for are ignored.
+#[derive(Deserialize)]
+struct NcduInfo {
+    name: String,
+    #[serde(default)]
+    asize: u64,
+    #[serde(default)]
+    dsize: u64,
+    /// Seconds since the Unix epoch, only in extended exports
+    mtime: Option<u64>,
+    /// Device, when it differs from the parent's
+    dev: Option<u64>,
+    ino: Option<u64>,
+    /// Set on files with more than one hard link
+    #[serde(default)]
+    hlnkc: bool,
+    #[serde(default)]
+    read_error: bool,
+    /// Why ncdu didn't descend: "pattern", "otherfs", "kernfs" or "frmlnk"
+    excluded: Option<String>,
+}
+
+/// An ncdu entry: a file is its info object, a directory an array of its info object
+/// followed by its children.
+enum NcduNode {
+    Entry(NcduInfo),
+    Dir(NcduInfo, Vec<NcduNode>),
+}
+
+impl<'de> Deserialize<'de> for NcduNode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(NcduNodeVisitor)
+    }
+}
+
+struct NcduNodeVisitor;
+
+impl<'de> Visitor<'de> for NcduNodeVisitor {
+    type Value = NcduNode;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an ncdu entry object or directory array")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<NcduNode, A::Error> {
+        NcduInfo::deserialize(MapAccessDeserializer::new(map)).map(NcduNode::Entry)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<NcduNode, A::Error> {
+        let info = seq
+            .next_element::<NcduInfo>()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let mut children = Vec::new();
+        while let Some(child) = seq.next_element::<NcduNode>()? {
+            children.push(child);
+        }
+        Ok(NcduNode::Dir(info, children))
+    }
+}
+
+fn read_ncdu(reader: impl BufRead, size_mode: SizeMode, totals: &mut Totals) -> io::Result<FileInfo> {
+    // Every directory level is a nested array, and real trees go deeper than serde_json's
+    // default limit; paths are bounded, so the depth is too
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    deserializer.disable_recursion_limit();
+    let (major, _minor, _meta, root): (u32, u32, IgnoredAny, NcduNode) = Deserialize::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    if major != export::NCDU_MAJOR_VERSION {
+        return Err(invalid_data(format!("Unsupported ncdu export version {}", major)));
+    }
+
+    let mut seen = HashSet::new();
+    ncdu_node(root, None, 0, size_mode, &mut seen, totals)
+        .ok_or_else(|| invalid_data("The export's root was excluded"))
+}
+
+/// Converts one ncdu entry and everything below it. Entries ncdu excluded by pattern are
+/// dropped and counted, like the scanner's own exclusions.
+fn ncdu_node(
+    node: NcduNode,
+    parent: Option<&Path>,
+    parent_dev: u64,
+    size_mode: SizeMode,
+    seen: &mut HashSet<(u64, u64)>,
+    totals: &mut Totals,
+) -> Option<FileInfo> {
+    let (info, children) = match node {
+        NcduNode::Entry(info) => (info, None),
+        NcduNode::Dir(info, children) => (info, Some(children)),
+    };
+
+    let path = export::ncdu_path(parent, &info.name);
+    let path_str = path.to_string_lossy().to_string();
+    let name = match parent {
+        Some(_) => info.name.clone(),
+        None => path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path_str.clone()),
+    };
+    let dev = info.dev.unwrap_or(parent_dev);
+    // A bogus mtime too far out to count in nanoseconds is as good as none
+    let mtime = info
+        .mtime
+        .and_then(|secs| i64::try_from(secs).ok())
+        .and_then(|secs| secs.checked_mul(1_000_000_000));
+
+    if info.read_error {
+        totals.record_error(&path_str);
+    }
+
+    match info.excluded.as_deref() {
+        Some("pattern") => {
+            totals.excluded_entries += 1;
+            return None;
+        }
+        Some(_) => {
+            return Some(FileInfo {
+                name,
+                path: path_str,
+                size: 0,
+                apparent_size: 0,
+                allocated_size: 0,
+                is_dir: true,
+                kind: NodeKind::MountPoint,
+                file_count: 0,
+                child_count: 0,
+                hard_link_duplicate: false,
+                link_target: None,
+                mtime,
+                ctime: None,
+                inode: None,
+                children: Vec::new(),
+            });
+        }
+        None => {}
+    }
+
+    if let Some(children) = children {
+        totals.directories += 1;
+        let children = children
+            .into_iter()
+            .filter_map(|child| ncdu_node(child, Some(&path), dev, size_mode, seen, totals))
+            .collect();
+        return Some(directory_node(name, path_str, NodeKind::Directory, None, mtime, None, children));
+    }
+
+    // Like the scanner, every inode is counted once and further links report zero bytes
+    let duplicate = info.hlnkc && info.ino.is_some_and(|ino| !seen.insert((dev, ino)));
+    if duplicate {
+        totals.hard_link_duplicates += 1;
+    }
+    let (apparent_size, allocated_size) = if duplicate { (0, 0) } else { (info.asize, info.dsize) };
+
+    Some(FileInfo {
+        name,
+        path: path_str,
+        size: match size_mode {
+            SizeMode::Apparent => apparent_size,
+            SizeMode::Allocated => allocated_size,
+        },
+        apparent_size,
+        allocated_size,
+        is_dir: false,
+        kind: NodeKind::File,
+        file_count: 1,
+        child_count: 0,
+        hard_link_duplicate: duplicate,
+        link_target: None,
+        mtime,
+        ctime: None,
+        inode: None,
+        children: Vec::new(),
+    })
+}
+
+/// Rebuilds the tree from `du -ab` lines (`<bytes>\t<path>`). An entry is a directory when
+/// other entries sit below it; the sizes `du` gives directories are left out, since the
+/// scanner also sizes a directory as the sum of its children. An empty directory can't be
+/// told apart from a file and shows up as one.
+fn read_du(reader: impl BufRead, totals: &mut Totals) -> io::Result<FileInfo> {
+    let mut entries = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (size, path) = line
+            .split_once('\t')
+            .ok_or_else(|| invalid_data(format!("Line {}: expected <size><TAB><path>", index + 1)))?;
+        let size: u64 = size
+            .trim()
+            .parse()
+            .map_err(|_| invalid_data(format!("Line {}: invalid size {:?}", index + 1, size)))?;
+        entries.push((PathBuf::from(path), size));
+    }
+
+    let root = entries
+        .iter()
+        .map(|(path, _)| path.clone())
+        .min_by_key(|path| path.components().count())
+        .ok_or_else(|| invalid_data("No entries in the du listing"))?;
+
+    let mut sizes = HashMap::new();
+    let mut children: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+    for (path, size) in entries {
+        if path != root {
+            if !path.starts_with(&root) {
+                return Err(invalid_data(format!("{} is outside {}", path.display(), root.display())));
+            }
+            if let Some(parent) = path.parent() {
+                children.entry(parent.to_path_buf()).or_default().push(path.clone());
+            }
+        }
+        sizes.insert(path, size);
+    }
+
+    Ok(du_node(&root, &sizes, &mut children, totals))
+}
+
+fn du_node(
+    path: &Path,
+    sizes: &HashMap<PathBuf, u64>,
+    children: &mut HashMap<PathBuf, Vec<PathBuf>>,
+    totals: &mut Totals,
+) -> FileInfo {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string());
+    let path_str = path.to_string_lossy().to_string();
+
+    if let Some(child_paths) = children.remove(path) {
+        totals.directories += 1;
+        let nodes = child_paths
+            .iter()
+            .map(|child| du_node(child, sizes, children, totals))
+            .collect();
+        return directory_node(name, path_str, NodeKind::Directory, None, None, None, nodes);
+    }
+
+    let size = sizes.get(path).copied().unwrap_or(0);
+    FileInfo {
+        name,
+        path: path_str,
+        size,
+        apparent_size: size,
+        allocated_size: size,
+        is_dir: false,
+        kind: NodeKind::File,
+        file_count: 1,
+        child_count: 0,
+        hard_link_duplicate: false,
+        link_target: None,
+        mtime: None,
+        ctime: None,
+        inode: None,
+        children: Vec::new(),
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn du(listing: &str) -> io::Result<(FileInfo, Totals)> {
+        let mut totals = Totals::default();
+        read_du(Cursor::new(listing), &mut totals).map(|root| (root, totals))
+    }
+
+    fn ncdu(export: &str, size_mode: SizeMode) -> io::Result<(FileInfo, Totals)> {
+        let mut totals = Totals::default();
+        read_ncdu(Cursor::new(export), size_mode, &mut totals).map(|root| (root, totals))
+    }
+
+    #[test]
+    fn du_rebuilds_directories_from_paths() {
+        let (root, totals) = du("4096\t/r/a/x\n10\t/r/a/y\n20\t/r/b\n4126\t/r/a\n\n4146\t/r\n").unwrap();
+
+        assert_eq!(root.path, "/r");
+        assert_eq!(root.size, 4126);
+        assert_eq!(root.file_count, 3);
+        assert_eq!(totals.directories, 2);
+        let a = &root.children[0];
+        assert_eq!((a.name.as_str(), a.is_dir, a.size, a.child_count), ("a", true, 4106, 2));
+        assert_eq!((root.children[1].name.as_str(), root.children[1].is_dir), ("b", false));
+    }
+
+    #[test]
+    fn du_rejects_malformed_listings() {
+        assert!(du("").is_err());
+        assert!(du("12 /r/a\n").is_err());
+        assert!(du("big\t/r/a\n").is_err());
+        assert!(du("1\t/r/a\n2\t/s/b\n").is_err());
+    }
+
+    #[test]
+    fn ncdu_reads_sizes_in_either_mode() {
+        let export = r#"[1,2,{"progname":"ncdu"},
+            [{"name":"/r","asize":4096,"dsize":4096},
+             {"name":"f","asize":10,"dsize":4096,"mtime":1700000000},
+             [{"name":"d"},{"name":"g","asize":5,"dsize":4096}]]]"#;
+
+        let (root, totals) = ncdu(export, SizeMode::Apparent).unwrap();
+        assert_eq!((root.name.as_str(), root.path.as_str()), ("r", "/r"));
+        // A directory's own entry size isn't part of the total, as in the scanner
+        assert_eq!(root.size, 15);
+        assert_eq!(root.file_count, 2);
+        assert_eq!(totals.directories, 2);
+        let f = root.children.iter().find(|child| child.name == "f").unwrap();
+        assert_eq!(f.path, "/r/f");
+        assert_eq!(f.mtime, Some(1_700_000_000_000_000_000));
+        assert_eq!(root.children.iter().find(|child| child.name == "d").unwrap().children[0].path, "/r/d/g");
+
+        let (root, _) = ncdu(export, SizeMode::Allocated).unwrap();
+        assert_eq!(root.size, 8192);
+    }
+
+    #[test]
+    fn ncdu_treats_out_of_range_mtimes_as_unknown() {
+        let export = r#"[1,2,{},
+            [{"name":"/r"},
+             {"name":"far","asize":1,"mtime":9300000000000000000},
+             {"name":"past_i64","asize":1,"mtime":18446744073709551615}]]"#;
+
+        let (root, _) = ncdu(export, SizeMode::Apparent).unwrap();
+        assert!(root.children.iter().all(|child| child.mtime.is_none()));
+    }
+
+    #[test]
+    fn ncdu_counts_hard_links_once() {
+        let export = r#"[1,2,{},[{"name":"/r","dev":1},
+            {"name":"a","asize":100,"dsize":4096,"ino":7,"hlnkc":true},
+            {"name":"b","asize":100,"dsize":4096,"ino":7,"hlnkc":true},
+            {"name":"c","asize":100,"dsize":4096,"ino":7}]]"#;
+
+        let (root, totals) = ncdu(export, SizeMode::Apparent).unwrap();
+        assert_eq!(root.size, 200);
+        assert_eq!(totals.hard_link_duplicates, 1);
+        assert_eq!(root.children.iter().filter(|child| child.hard_link_duplicate).count(), 1);
+    }
+
+    #[test]
+    fn ncdu_drops_pattern_exclusions_and_keeps_mount_points() {
+        let export = r#"[1,2,{},[{"name":"/r"},
+            {"name":"skipped","asize":100,"excluded":"pattern"},
+            {"name":"mnt","excluded":"otherfs"},
+            {"name":"bad","read_error":true}]]"#;
+
+        let (root, totals) = ncdu(export, SizeMode::Apparent).unwrap();
+        assert_eq!(totals.excluded_entries, 1);
+        assert_eq!(totals.error_count, 1);
+        assert_eq!(totals.errors[0].path, "/r/bad");
+        let names: Vec<&str> = root.children.iter().map(|child| child.name.as_str()).collect();
+        assert!(!names.contains(&"skipped"));
+        let mnt = root.children.iter().find(|child| child.name == "mnt").unwrap();
+        assert_eq!(mnt.kind, NodeKind::MountPoint);
+    }
+
+    #[test]
+    fn ncdu_reads_trees_deeper_than_the_json_recursion_limit() {
+        let depth = 300;
+        let mut export = String::from(r#"[1,2,{},[{"name":"/r"}"#);
+        for level in 0..depth {
+            export.push_str(&format!(r#",[{{"name":"d{}"}}"#, level));
+        }
+        export.push_str(r#",{"name":"f","asize":1}"#);
+        export.push_str(&"]".repeat(depth + 2));
+
+        let (root, totals) = ncdu(&export, SizeMode::Apparent).unwrap();
+        assert_eq!(root.size, 1);
+        assert_eq!(totals.directories, depth + 1);
+    }
+
+    #[test]
+    fn ncdu_rejects_other_major_versions() {
+        assert!(ncdu(r#"[2,0,{},{"name":"/r"}]"#, SizeMode::Apparent).is_err());
+    }
+}
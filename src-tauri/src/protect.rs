@@ -0,0 +1,288 @@
+//! Paths the app refuses to delete, move or clean up, whatever a frontend asks for.
+//!
+//! A target is refused when it is a protected path or one of its ancestors, since removing
+//! `/home` takes the home directory with it. Everything below a protected path is fair game.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::scanner::mount_points;
+
+/// System directories that are never touched.
+#[cfg(unix)]
+const SYSTEM_PATHS: &[&str] = &[
+    "/", "/bin", "/boot", "/dev", "/etc", "/home", "/lib", "/lib32", "/lib64", "/media", "/mnt",
+    "/opt", "/proc", "/root", "/run", "/sbin", "/srv", "/sys", "/tmp", "/usr", "/var",
+    // macOS
+    "/Applications", "/Library", "/System", "/Users", "/Volumes", "/private",
+];
+
+/// The file in the app data directory holding the paths the user protected, as a JSON array
+/// of strings. Every frontend reads the same one.
+pub const USER_PATHS_FILE: &str = "protected_paths.json";
+
+/// Environment variables naming the Windows system directories.
+#[cfg(windows)]
+const SYSTEM_PATH_VARS: &[&str] = &["SystemDrive", "SystemRoot", "ProgramFiles", "ProgramFiles(x86)", "ProgramData"];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtectionReason {
+    System,
+    Home,
+    MountRoot,
+    /// Root of a scan the app currently holds
+    ScanRoot,
+    /// Added by the user in the settings
+    User,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProtectedPath {
+    pub path: String,
+    pub reason: ProtectionReason,
+}
+
+/// Returned when an operation would remove a protected path.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProtectedPathError {
+    /// What the operation was asked to act on
+    pub path: String,
+    /// The protected path it is, or contains
+    pub protected_path: String,
+    pub reason: ProtectionReason,
+}
+
+impl fmt::Display for ProtectedPathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if Path::new(&self.path) == Path::new(&self.protected_path) {
+            write!(f, "{} is protected", self.path)
+        } else {
+            write!(f, "{} contains the protected path {}", self.path, self.protected_path)
+        }
+    }
+}
+
+impl std::error::Error for ProtectedPathError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProtectedPaths {
+    entries: Vec<(PathBuf, ProtectionReason)>,
+}
+
+impl ProtectedPaths {
+    /// The built-in deny list: system directories, the home directory and every mount root.
+    pub fn builtin() -> Self {
+        let mut protected = ProtectedPaths::default();
+
+        #[cfg(unix)]
+        for path in SYSTEM_PATHS {
+            protected.add(path, ProtectionReason::System);
+        }
+        #[cfg(windows)]
+        for path in SYSTEM_PATH_VARS.iter().filter_map(std::env::var_os) {
+            protected.add(path, ProtectionReason::System);
+        }
+
+        if let Some(home) = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+            protected.add(home, ProtectionReason::Home);
+        }
+        for mount_point in mount_points() {
+            protected.add(mount_point, ProtectionReason::MountRoot);
+        }
+        protected
+    }
+
+    /// The built-in list plus the paths the user protected in `data_dir`. Fails when the
+    /// user's list exists but can't be read, so nothing is deleted with a partial list.
+    pub fn load(data_dir: &Path) -> io::Result<Self> {
+        let mut protected = ProtectedPaths::builtin();
+        for path in read_user_paths(data_dir)? {
+            protected.add(path, ProtectionReason::User);
+        }
+        Ok(protected)
+    }
+
+    /// Protects `path` as given and, when it differs, as it resolves on disk, so neither
+    /// spelling gets through.
+    pub fn add(&mut self, path: impl AsRef<Path>, reason: ProtectionReason) {
+        let path = path.as_ref();
+        if path.as_os_str().is_empty() {
+            return;
+        }
+        if let Ok(resolved) = fs::canonicalize(path) {
+            if resolved != path {
+                self.entries.push((resolved, reason));
+            }
+        }
+        self.entries.push((path.to_path_buf(), reason));
+    }
+
+    pub fn list(&self) -> Vec<ProtectedPath> {
+        self.entries
+            .iter()
+            .map(|(path, reason)| ProtectedPath {
+                path: path.to_string_lossy().to_string(),
+                reason: *reason,
+            })
+            .collect()
+    }
+
+    /// Fails when `target` is a protected path or an ancestor of one.
+    pub fn check(&self, target: &Path) -> Result<(), ProtectedPathError> {
+        let resolved = resolve(target).unwrap_or_else(|_| target.to_path_buf());
+        let hit = self
+            .entries
+            .iter()
+            .find(|(protected, _)| protected.starts_with(&resolved) || protected.starts_with(target));
+
+        match hit {
+            Some((protected, reason)) => Err(ProtectedPathError {
+                path: target.to_string_lossy().to_string(),
+                protected_path: protected.to_string_lossy().to_string(),
+                reason: *reason,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The paths the user protected on top of the built-in ones. No file means none.
+pub fn read_user_paths(data_dir: &Path) -> io::Result<Vec<String>> {
+    let file = data_dir.join(USER_PATHS_FILE);
+    let contents = match fs::read(&file) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(io::Error::new(e.kind(), format!("{}: {}", file.display(), e))),
+    };
+    serde_json::from_slice(&contents)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", file.display(), e)))
+}
+
+/// Replaces the paths the user protected. Only absolute paths are accepted.
+pub fn write_user_paths(data_dir: &Path, paths: &[String]) -> io::Result<()> {
+    if let Some(relative) = paths.iter().find(|path| !Path::new(path).is_absolute()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Protected paths must be absolute: {}", relative),
+        ));
+    }
+    fs::create_dir_all(data_dir)?;
+    fs::write(data_dir.join(USER_PATHS_FILE), serde_json::to_vec_pretty(paths)?)
+}
+
+/// Resolves `..`, `.` and symlinks in the parent of `path` but not `path` itself: removing a
+/// link to `/usr` only removes the link.
+pub(crate) fn resolve(path: &Path) -> io::Result<PathBuf> {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => Ok(fs::canonicalize(parent)?.join(name)),
+        (_, Some(name)) => Ok(std::env::current_dir()?.join(name)),
+        // `/`, or a path ending in `..`
+        _ => fs::canonicalize(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A scratch directory, resolved so the expected paths match what `check` compares against.
+    /// It is removed when the `TempDir` is dropped.
+    fn scratch_dir() -> (TempDir, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let path = fs::canonicalize(dir.path()).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn refuses_protected_paths_and_their_ancestors_only() {
+        let (_scratch, dir) = scratch_dir();
+        let protected_dir = dir.join("parent").join("protected");
+        fs::create_dir_all(protected_dir.join("inside")).unwrap();
+        fs::create_dir_all(dir.join("sibling")).unwrap();
+
+        let mut protected = ProtectedPaths::default();
+        protected.add(&protected_dir, ProtectionReason::User);
+
+        let error = protected.check(&protected_dir).unwrap_err();
+        assert_eq!(Path::new(&error.protected_path), protected_dir);
+        assert_eq!(error.reason, ProtectionReason::User);
+        assert!(protected.check(&dir.join("parent")).is_err());
+        assert!(protected.check(&dir).is_err());
+        assert!(protected.check(&protected_dir.join("inside")).is_ok());
+        assert!(protected.check(&dir.join("sibling")).is_ok());
+        // A shared name prefix doesn't make a path an ancestor
+        assert!(protected.check(&dir.join("parent").join("protect")).is_ok());
+    }
+
+    #[test]
+    fn catches_dot_dot_spellings() {
+        let (_scratch, dir) = scratch_dir();
+        let protected_dir = dir.join("protected");
+        fs::create_dir_all(dir.join("other")).unwrap();
+        fs::create_dir_all(&protected_dir).unwrap();
+
+        let mut protected = ProtectedPaths::default();
+        protected.add(&protected_dir, ProtectionReason::User);
+
+        assert!(protected.check(&dir.join("other").join("..").join("protected")).is_err());
+        assert!(protected.check(&protected_dir.join("..")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn catches_symlinked_spellings() {
+        let (_scratch, dir) = scratch_dir();
+        let real = dir.join("real");
+        fs::create_dir_all(real.join("protected")).unwrap();
+        std::os::unix::fs::symlink(&real, dir.join("link")).unwrap();
+        std::os::unix::fs::symlink(real.join("protected"), dir.join("shortcut")).unwrap();
+
+        // Protected through the link, refused through the real path
+        let mut protected = ProtectedPaths::default();
+        protected.add(dir.join("link").join("protected"), ProtectionReason::User);
+        assert!(protected.check(&real.join("protected")).is_err());
+        assert!(protected.check(&real).is_err());
+
+        // Protected by the real path, refused through the link
+        let mut protected = ProtectedPaths::default();
+        protected.add(real.join("protected"), ProtectionReason::User);
+        assert!(protected.check(&dir.join("link").join("protected")).is_err());
+
+        // Removing a link to a protected directory only removes the link
+        assert!(protected.check(&dir.join("shortcut")).is_ok());
+        assert!(protected.check(&dir.join("link")).is_ok());
+    }
+
+    #[test]
+    fn loads_the_users_paths_on_top_of_the_builtin_ones() {
+        let (_scratch, dir) = scratch_dir();
+        assert!(read_user_paths(&dir).unwrap().is_empty());
+
+        let user_path = dir.join("keep").to_string_lossy().to_string();
+        write_user_paths(&dir, std::slice::from_ref(&user_path)).unwrap();
+        assert_eq!(read_user_paths(&dir).unwrap(), vec![user_path.clone()]);
+
+        let loaded = ProtectedPaths::load(&dir).unwrap();
+        assert!(loaded
+            .list()
+            .iter()
+            .any(|entry| entry.path == user_path && entry.reason == ProtectionReason::User));
+        assert_eq!(loaded.check(Path::new(&user_path)).unwrap_err().reason, ProtectionReason::User);
+    }
+
+    #[test]
+    fn rejects_relative_and_unreadable_lists() {
+        let (_scratch, dir) = scratch_dir();
+        let error = write_user_paths(&dir, &["relative/path".to_string()]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        fs::write(dir.join(USER_PATHS_FILE), b"{not json").unwrap();
+        assert_eq!(ProtectedPaths::load(&dir).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
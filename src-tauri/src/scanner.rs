@@ -0,0 +1,1553 @@
+//! The scan engine: walking a tree in parallel, sizing it and reporting progress. Nothing in
+//! here knows about Tauri, so the desktop app and the headless CLI share it unchanged.
+
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Instant, UNIX_EPOCH};
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use rayon::prelude::*;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    File,
+    Directory,
+    /// Synthetic node standing in for the children folded away by the display limit
+    Other,
+    /// Directory on another filesystem (or a pseudo-filesystem) that the scan did not descend into
+    MountPoint,
+    /// Symbolic link; a followed link to a directory still carries the target's children
+    Symlink,
+}
+
+impl NodeKind {
+    /// The name used in JSON, for text formats that write the kind on its own.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NodeKind::File => "file",
+            NodeKind::Directory => "directory",
+            NodeKind::Other => "other",
+            NodeKind::MountPoint => "mount_point",
+            NodeKind::Symlink => "symlink",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SymlinkPolicy {
+    /// Report links as leaves without touching their targets, like `du -P`
+    #[default]
+    DontFollow,
+    /// Descend into link targets, visiting each directory (device, inode) only once
+    Follow,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeMode {
+    /// Logical file length, like `du --apparent-size`
+    #[default]
+    Apparent,
+    /// Blocks actually allocated on disk, like plain `du`
+    Allocated,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileInfo {
+    pub name: String,
+    pub path: String,
+    /// Size according to the scan's `SizeMode`; this is what sorting and totals use
+    pub size: u64,
+    pub apparent_size: u64,
+    pub allocated_size: u64,
+    pub is_dir: bool,
+    pub kind: NodeKind,
+    /// Number of files in this subtree (1 for a plain file)
+    pub file_count: usize,
+    /// Number of direct children, which stays accurate when `children` is left out of a view
+    pub child_count: usize,
+    /// Another link (hard, or a followed symlink) to an inode already counted elsewhere in this
+    /// scan; its bytes are reported as zero so every inode is counted once
+    pub hard_link_duplicate: bool,
+    /// Where a symlink points, exactly as stored in the link
+    pub link_target: Option<String>,
+    /// Last modification, in nanoseconds since the Unix epoch
+    pub mtime: Option<i64>,
+    /// Last status change (Unix only), in nanoseconds since the Unix epoch
+    pub ctime: Option<i64>,
+    /// (device, inode) of a file other entries may also reach, so incremental scans can
+    /// still count it once: files with several hard links, or every file when symlinks are
+    /// followed
+    pub inode: Option<(u64, u64)>,
+    pub children: Vec<FileInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScanResult {
+    pub scan_id: u64,
+    pub root: FileInfo,
+    pub size_mode: SizeMode,
+    pub total_size: u64,
+    pub file_count: usize,
+    pub directory_count: usize,
+    /// Incremental scans only: directories whose listing was taken from the baseline
+    pub reused_directories: usize,
+    /// Incremental scans only: baseline directories that had changed and were read again
+    pub rescanned_directories: usize,
+    pub error_count: usize,
+    /// The first `MAX_REPORTED_ERRORS` failures, in the order they happened
+    pub errors: Vec<ScanError>,
+    /// Every failure counted by kind, largest group first, including those past the cap
+    pub error_groups: Vec<ScanErrorGroup>,
+    /// Roughly how much data sits in what couldn't be read: the filesystem's used bytes minus
+    /// what the scan counted, directories' own blocks included. Only known for complete scans of a whole mount with nothing
+    /// excluded, and on the low side when the scan crossed into other filesystems
+    pub unreadable_bytes_estimate: Option<u64>,
+    /// Extra hard links whose bytes were not counted again
+    pub hard_link_duplicates: usize,
+    /// Entries skipped by the exclude patterns or the include filter
+    pub excluded_entries: usize,
+    /// Bytes of skipped files; excluded directories are never read, so their contents aren't included
+    pub excluded_bytes: u64,
+    pub cancelled: bool,
+    pub timing: ScanTiming,
+    /// What the scan was run with, so an incremental rescan can tell whether it may reuse this
+    /// tree. Unknown for imported results
+    pub options: Option<ScanOptions>,
+}
+
+/// Wall-clock time spent in each phase of a scan.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ScanTiming {
+    pub enumerate_ms: u64,
+    pub aggregate_ms: u64,
+    pub serialize_ms: u64,
+    pub total_ms: u64,
+}
+
+/// Payload of the `scan-complete` event sent once a scan's result is ready.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScanSummary {
+    pub scan_id: u64,
+    pub cancelled: bool,
+    pub file_count: usize,
+    pub directory_count: usize,
+    pub total_size: u64,
+    pub error_count: usize,
+    pub timing: ScanTiming,
+    pub files_per_sec: f64,
+    pub bytes_per_sec: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanPhase {
+    /// Walking the filesystem
+    Enumerating,
+    /// Walk finished; totals and error groups are being put together
+    Aggregating,
+    /// Building the trimmed view that is sent to the frontend
+    Serializing,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanErrorKind {
+    PermissionDenied,
+    /// The entry vanished between being listed and being read
+    NotFound,
+    Io,
+}
+
+impl From<std::io::ErrorKind> for ScanErrorKind {
+    fn from(kind: std::io::ErrorKind) -> Self {
+        match kind {
+            std::io::ErrorKind::PermissionDenied => ScanErrorKind::PermissionDenied,
+            std::io::ErrorKind::NotFound => ScanErrorKind::NotFound,
+            _ => ScanErrorKind::Io,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScanError {
+    pub path: String,
+    pub kind: ScanErrorKind,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScanErrorGroup {
+    pub kind: ScanErrorKind,
+    pub count: usize,
+}
+
+/// Keeps scan results bounded when a whole tree is unreadable.
+pub(crate) const MAX_REPORTED_ERRORS: usize = 1000;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ScanOptions {
+    /// Children shown per directory before the rest are folded into an "other" node (0 = no limit)
+    pub display_limit: usize,
+    /// Levels below the root included in the returned `ScanResult`; deeper levels come from `get_children`
+    pub initial_depth: usize,
+    pub size_mode: SizeMode,
+    /// Stay on the filesystem of the root, like `du -x`
+    pub one_file_system: bool,
+    pub symlinks: SymlinkPolicy,
+    /// Globs for entries to skip. Patterns containing `/` match the full path (`/home/*/.cache`,
+    /// `**/.git`), others match the entry name (`*.iso`)
+    pub exclude: Vec<String>,
+    /// When non-empty, only files with one of these extensions are kept (case-insensitive, no dot)
+    pub include_extensions: Vec<String>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            display_limit: 50,
+            initial_depth: 3,
+            size_mode: SizeMode::default(),
+            one_file_system: false,
+            symlinks: SymlinkPolicy::default(),
+            exclude: Vec::new(),
+            include_extensions: Vec::new(),
+        }
+    }
+}
+
+impl ScanOptions {
+    /// Whether a scan with `other` lists the same entries, which is what an incremental scan
+    /// needs from its baseline. The size mode doesn't matter as reused entries are stat'ed again.
+    pub fn lists_same_entries(&self, other: &ScanOptions) -> bool {
+        self.one_file_system == other.one_file_system
+            && self.symlinks == other.symlinks
+            && self.exclude == other.exclude
+            && self.include_extensions == other.include_extensions
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    #[default]
+    Size,
+    Name,
+    Count,
+    /// Most recently modified first
+    Mtime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScanProgress {
+    pub scan_id: u64,
+    pub phase: ScanPhase,
+    pub current_path: String,
+    pub files_processed: usize,
+    pub directories_processed: usize,
+    pub errors_so_far: usize,
+    pub total_size_so_far: u64,
+    pub elapsed_ms: u64,
+    pub files_per_sec: f64,
+    pub bytes_per_sec: f64,
+    /// Remaining time, only when there is a percentage to extrapolate from
+    pub eta_ms: Option<u64>,
+    /// Expected number of entries (files and directories) in the whole scan
+    pub estimated_total: Option<usize>,
+    /// Expected bytes allocated on disk by the whole scan
+    pub estimated_total_bytes: Option<u64>,
+    pub percent_complete: Option<f64>,
+    pub estimate_method: EstimateMethod,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EstimateMethod {
+    /// Nothing to base an estimate on (unsupported platform, or statvfs failed)
+    #[default]
+    None,
+    /// The root is a mount point, so the filesystem's used bytes and inodes are the totals
+    Filesystem,
+    /// Extrapolated from the share of the root's entries already finished, capped by the
+    /// filesystem's usage
+    Heuristic,
+}
+
+/// Receives progress while a scan runs. It's called from the rayon workers, so it has to be
+/// `Sync`; the GUI forwards each update as an event, the CLI draws a status line.
+pub trait ProgressSink: Sync {
+    fn progress(&self, progress: &ScanProgress);
+}
+
+/// Sink for scans nobody watches, such as the watcher's rescans of changed paths.
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn progress(&self, _progress: &ScanProgress) {}
+}
+
+pub(crate) fn per_second(amount: f64, elapsed_ms: u64) -> f64 {
+    if elapsed_ms == 0 {
+        return 0.0;
+    }
+    amount * 1000.0 / elapsed_ms as f64
+}
+
+/// Scans `root_path` in parallel and returns the full, untrimmed tree. `sink` is called
+/// from whichever worker thread happens to be due, at most every `PROGRESS_INTERVAL_MS`.
+///
+/// With a `previous` tree of the same root the scan is incremental: directories whose
+/// timestamps haven't moved have their listing taken over from it instead of being read
+/// again. The baseline must come from a scan whose options `lists_same_entries` with these,
+/// since reused listings aren't re-filtered.
+///
+/// A relative `root_path` is resolved first, so the tree's paths are always absolute and the
+/// path patterns of `options` match the same way whatever directory the caller runs in.
+pub fn scan_tree(
+    root_path: &Path,
+    scan_id: u64,
+    options: &ScanOptions,
+    previous: Option<&FileInfo>,
+    cancel_flag: &AtomicBool,
+    sink: &dyn ProgressSink,
+) -> Result<ScanResult, Box<dyn std::error::Error>> {
+    let Ok(root_path) = root_path.canonicalize() else {
+        return Err("Path does not exist".into());
+    };
+    let root_path = root_path.as_path();
+
+    let state = ScanState::new(root_path, scan_id, options, cancel_flag, sink, None)?;
+
+    // Fast parallel directory scan
+    let root = scan_directory_parallel(root_path, previous, &state)?;
+    let enumerate_ms = state.started.elapsed().as_millis() as u64;
+
+    let aggregate_start = Instant::now();
+    state.emit_progress(&root.path, ScanPhase::Aggregating);
+    let (error_count, error_groups) = state.errors.groups();
+    let hard_link_duplicates = state.inodes.files.lock().unwrap().values().map(|links| links - 1).sum();
+    let cancelled = cancel_flag.load(Ordering::Relaxed);
+    let complete = !cancelled && state.filter.excluded_entries.load(Ordering::Relaxed) == 0;
+    let unreadable_bytes_estimate = (error_count > 0 && complete)
+        .then(|| state.estimate.unaccounted_bytes(state.total_allocated.load(Ordering::Relaxed)))
+        .flatten();
+    let aggregate_ms = aggregate_start.elapsed().as_millis() as u64;
+
+    Ok(ScanResult {
+        scan_id,
+        root,
+        size_mode: options.size_mode,
+        total_size: state.total_size.load(Ordering::Relaxed),
+        file_count: state.total_files.load(Ordering::Relaxed),
+        directory_count: state.total_dirs.load(Ordering::Relaxed),
+        reused_directories: state.reused_dirs.load(Ordering::Relaxed),
+        rescanned_directories: state.rescanned_dirs.load(Ordering::Relaxed),
+        error_count,
+        errors: state.errors.reported.into_inner().unwrap(),
+        error_groups,
+        unreadable_bytes_estimate,
+        hard_link_duplicates,
+        excluded_entries: state.filter.excluded_entries.load(Ordering::Relaxed),
+        excluded_bytes: state.filter.excluded_bytes.load(Ordering::Relaxed),
+        cancelled,
+        timing: ScanTiming {
+            enumerate_ms,
+            aggregate_ms,
+            serialize_ms: 0,
+            total_ms: enumerate_ms + aggregate_ms,
+        },
+        options: Some(options.clone()),
+    })
+}
+
+/// Never set: entries scanned again for an existing tree run to completion.
+static NOT_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Scans entries somewhere below the root of an earlier scan, the way that scan reached them:
+/// as entries of their parents, so symlinks follow the scan's policy and mount boundaries are
+/// those of `root`. Files in `counted` are already counted elsewhere in the tree (see
+/// `counted_inodes`) and come out as duplicates.
+///
+/// Setting one up finds the mounts and compiles the filters, so a batch of changed paths
+/// shares a single one; files reached twice within the batch are counted once.
+pub struct EntryScanner<'a> {
+    state: ScanState<'a>,
+}
+
+impl<'a> EntryScanner<'a> {
+    pub fn new(root: &Path, options: &'a ScanOptions, counted: &'a HashSet<(u64, u64)>) -> Result<Self, globset::Error> {
+        let state = ScanState::new(root, 0, options, &NOT_CANCELLED, &NoProgress, Some(counted))?;
+        Ok(EntryScanner { state })
+    }
+
+    /// Whether the scan's filters would have skipped `path`.
+    pub fn excludes(&self, path: &Path, is_dir: bool) -> bool {
+        self.state.filter.excludes(path, is_dir)
+    }
+
+    /// Returns `None` when `path` can't be read.
+    pub fn scan(&self, path: &Path) -> Option<FileInfo> {
+        scan_directory_parallel(path, None, &self.state).ok()
+    }
+}
+
+/// The files in `node` whose bytes it counts and that other entries may reach as well, for
+/// passing to `scan_entry`.
+pub fn counted_inodes(node: &FileInfo) -> HashSet<(u64, u64)> {
+    let mut counted = HashSet::new();
+    collect_counted_inodes(node, &mut counted);
+    counted
+}
+
+fn collect_counted_inodes(node: &FileInfo, counted: &mut HashSet<(u64, u64)>) {
+    if let Some(key) = node.inode.filter(|_| !node.hard_link_duplicate) {
+        counted.insert(key);
+    }
+    for child in &node.children {
+        collect_counted_inodes(child, counted);
+    }
+}
+
+/// Directories in `node`, itself included, counted the way a scan counts them: mount points
+/// it stopped at and directories it had already been to don't count.
+pub fn directory_count(node: &FileInfo) -> usize {
+    if !node.is_dir || node.kind == NodeKind::MountPoint || node.hard_link_duplicate {
+        return 0;
+    }
+    1 + node.children.iter().map(directory_count).sum::<usize>()
+}
+
+/// Minimum time between two `scan-progress` events.
+const PROGRESS_INTERVAL_MS: u64 = 50;
+
+/// Files a worker visits between looks at the clock; reading it for every file is
+/// measurable once trees reach millions of entries.
+const PROGRESS_CHECK_EVERY: u32 = 256;
+
+thread_local! {
+    static FILES_SINCE_PROGRESS_CHECK: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Everything the workers of one scan share. Counters on the per-file path are atomics so
+/// rayon workers never wait on each other; mutexes are left for rare events (errors, files
+/// with several hard links).
+struct ScanState<'a> {
+    scan_id: u64,
+    options: &'a ScanOptions,
+    cancel_flag: &'a AtomicBool,
+    sink: &'a dyn ProgressSink,
+    started: Instant,
+    total_files: AtomicUsize,
+    total_dirs: AtomicUsize,
+    /// Directories of an incremental scan taken over from the baseline, and those read again
+    reused_dirs: AtomicUsize,
+    rescanned_dirs: AtomicUsize,
+    total_size: AtomicU64,
+    /// Allocated bytes so far whatever the size mode, to compare against the filesystem's usage.
+    /// Unlike the tree's sizes this includes the blocks directories take up themselves
+    total_allocated: AtomicU64,
+    estimate: ProgressEstimate,
+    /// Milliseconds after `started` at which the last progress event went out
+    last_progress_ms: AtomicU64,
+    errors: ErrorLog,
+    inodes: InodeTracker<'a>,
+    boundaries: MountBoundaries,
+    filter: ScanFilter,
+}
+
+impl<'a> ScanState<'a> {
+    fn new(
+        root: &Path,
+        scan_id: u64,
+        options: &'a ScanOptions,
+        cancel_flag: &'a AtomicBool,
+        sink: &'a dyn ProgressSink,
+        counted_elsewhere: Option<&'a HashSet<(u64, u64)>>,
+    ) -> Result<Self, globset::Error> {
+        Ok(ScanState {
+            scan_id,
+            options,
+            cancel_flag,
+            sink,
+            started: Instant::now(),
+            total_files: AtomicUsize::new(0),
+            total_dirs: AtomicUsize::new(0),
+            reused_dirs: AtomicUsize::new(0),
+            rescanned_dirs: AtomicUsize::new(0),
+            total_size: AtomicU64::new(0),
+            total_allocated: AtomicU64::new(0),
+            estimate: ProgressEstimate::new(root),
+            last_progress_ms: AtomicU64::new(0),
+            errors: ErrorLog::default(),
+            inodes: InodeTracker {
+                counted_elsewhere,
+                ..InodeTracker::default()
+            },
+            boundaries: MountBoundaries::new(root, options.one_file_system),
+            filter: ScanFilter::new(options)?,
+        })
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel_flag.load(Ordering::Relaxed)
+    }
+
+    fn record_file(&self, path: &Path, file_count: usize, size: u64, allocated: u64) {
+        self.total_files.fetch_add(file_count, Ordering::Relaxed);
+        self.total_size.fetch_add(size, Ordering::Relaxed);
+        self.total_allocated.fetch_add(allocated, Ordering::Relaxed);
+
+        if self.progress_due() {
+            self.emit_progress(&path.to_string_lossy(), ScanPhase::Enumerating);
+        }
+    }
+
+    fn record_directory(&self, metadata: &fs::Metadata) {
+        self.total_dirs.fetch_add(1, Ordering::Relaxed);
+        self.total_allocated.fetch_add(allocated_size(metadata), Ordering::Relaxed);
+    }
+
+    /// Throttles progress events to one per `PROGRESS_INTERVAL_MS` across all workers.
+    fn progress_due(&self) -> bool {
+        let check_clock = FILES_SINCE_PROGRESS_CHECK.with(|since_check| {
+            let visited = since_check.get() + 1;
+            since_check.set(if visited >= PROGRESS_CHECK_EVERY { 0 } else { visited });
+            visited >= PROGRESS_CHECK_EVERY
+        });
+        if !check_clock {
+            return false;
+        }
+
+        let now_ms = self.started.elapsed().as_millis() as u64;
+        let last_ms = self.last_progress_ms.load(Ordering::Relaxed);
+        if now_ms.saturating_sub(last_ms) < PROGRESS_INTERVAL_MS {
+            return false;
+        }
+
+        // Only the worker that wins the exchange emits; the others carry on instead of waiting
+        self.last_progress_ms
+            .compare_exchange(last_ms, now_ms, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    fn emit_progress(&self, current_path: &str, phase: ScanPhase) {
+        let files_processed = self.total_files.load(Ordering::Relaxed);
+        let total_size_so_far = self.total_size.load(Ordering::Relaxed);
+        let allocated_so_far = self.total_allocated.load(Ordering::Relaxed);
+        let elapsed_ms = self.started.elapsed().as_millis() as u64;
+        let projection = self.estimate.project(files_processed, allocated_so_far);
+
+        let eta_ms = projection
+            .percent
+            .filter(|percent| *percent > 0.0)
+            .map(|percent| (elapsed_ms as f64 * (100.0 - percent) / percent) as u64);
+
+        self.sink.progress(&ScanProgress {
+            scan_id: self.scan_id,
+            phase,
+            current_path: current_path.to_string(),
+            files_processed,
+            directories_processed: self.total_dirs.load(Ordering::Relaxed),
+            errors_so_far: self.errors.total.load(Ordering::Relaxed),
+            total_size_so_far,
+            elapsed_ms,
+            files_per_sec: per_second(files_processed as f64, elapsed_ms),
+            bytes_per_sec: per_second(total_size_so_far as f64, elapsed_ms),
+            eta_ms,
+            estimated_total: projection.entries,
+            estimated_total_bytes: projection.bytes,
+            percent_complete: projection.percent,
+            estimate_method: self.estimate.method,
+        });
+    }
+}
+
+/// Used space on the filesystem holding the scan root, from statvfs.
+struct FilesystemUsage {
+    used_bytes: u64,
+    used_inodes: u64,
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)] // statvfs field widths differ between platforms
+fn filesystem_usage(path: &Path) -> Option<FilesystemUsage> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statvfs only writes into the zeroed struct we hand it, and c_path is NUL-terminated
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+
+    let used_blocks = (stats.f_blocks as u64).saturating_sub(stats.f_bfree as u64);
+    Some(FilesystemUsage {
+        used_bytes: used_blocks * stats.f_frsize as u64,
+        used_inodes: (stats.f_files as u64).saturating_sub(stats.f_ffree as u64),
+    })
+}
+
+#[cfg(not(unix))]
+fn filesystem_usage(_path: &Path) -> Option<FilesystemUsage> {
+    None
+}
+
+/// A directory is a mount point when its parent lives on another device (or it has no parent).
+fn is_mount_point(path: &Path) -> bool {
+    let device = |p: &Path| fs::metadata(p).ok().and_then(|meta| device_id(&meta));
+    match path.parent() {
+        None => true,
+        Some(parent) => match (device(path), device(parent)) {
+            (Some(own), Some(parent)) => own != parent,
+            _ => false,
+        },
+    }
+}
+
+#[derive(Default)]
+struct Projection {
+    entries: Option<usize>,
+    bytes: Option<u64>,
+    percent: Option<f64>,
+}
+
+/// Works out how far along a scan is. Scanning a whole mount point lets statvfs give the totals
+/// directly; anything else extrapolates from how many of the root's entries are done.
+struct ProgressEstimate {
+    method: EstimateMethod,
+    usage: Option<FilesystemUsage>,
+    top_level_total: AtomicUsize,
+    top_level_done: AtomicUsize,
+}
+
+impl ProgressEstimate {
+    fn new(root: &Path) -> Self {
+        let usage = filesystem_usage(root);
+        let method = match &usage {
+            None => EstimateMethod::None,
+            Some(_) if is_mount_point(root) => EstimateMethod::Filesystem,
+            Some(_) => EstimateMethod::Heuristic,
+        };
+
+        Self {
+            method,
+            usage,
+            top_level_total: AtomicUsize::new(0),
+            top_level_done: AtomicUsize::new(0),
+        }
+    }
+
+    fn project(&self, files_processed: usize, allocated_so_far: u64) -> Projection {
+        let Some(usage) = &self.usage else {
+            return Projection::default();
+        };
+
+        match self.method {
+            EstimateMethod::None => Projection::default(),
+            EstimateMethod::Filesystem => Projection {
+                entries: Some(usage.used_inodes as usize),
+                bytes: Some(usage.used_bytes),
+                percent: Some(percent_of(allocated_so_far, usage.used_bytes)),
+            },
+            EstimateMethod::Heuristic => {
+                let total = self.top_level_total.load(Ordering::Relaxed);
+                let done = self.top_level_done.load(Ordering::Relaxed);
+                if total == 0 || done == 0 {
+                    return Projection::default();
+                }
+
+                let fraction = done as f64 / total as f64;
+                let entries = (files_processed as f64 / fraction) as u64;
+                let bytes = (allocated_so_far as f64 / fraction) as u64;
+                Projection {
+                    entries: Some(entries.min(usage.used_inodes) as usize),
+                    bytes: Some(bytes.min(usage.used_bytes)),
+                    percent: Some(fraction * 100.0),
+                }
+            }
+        }
+    }
+
+    /// Used bytes on the filesystem that a finished scan of its whole mount didn't find.
+    fn unaccounted_bytes(&self, allocated_scanned: u64) -> Option<u64> {
+        match (self.method, &self.usage) {
+            (EstimateMethod::Filesystem, Some(usage)) => Some(usage.used_bytes.saturating_sub(allocated_scanned)),
+            _ => None,
+        }
+    }
+}
+
+fn percent_of(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        return 100.0;
+    }
+    (part as f64 / whole as f64 * 100.0).min(100.0)
+}
+
+/// Scans one entry. `previous` is the same entry in the baseline tree of an incremental scan.
+fn scan_directory_parallel(
+    path: &Path,
+    previous: Option<&FileInfo>,
+    state: &ScanState,
+) -> Result<FileInfo, Box<dyn std::error::Error>> {
+    let options = state.options;
+    let link_metadata = match fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(e) => {
+            state.errors.record(path, &e);
+            return Err("Cannot read metadata".into());
+        }
+    };
+
+    let link_target = link_metadata.file_type().is_symlink().then(|| {
+        fs::read_link(path)
+            .map(|target| target.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+
+    // The scan root is always resolved; anything below it only when the policy says so.
+    // A dangling link keeps its own metadata and ends up as a leaf.
+    let is_root = state.boundaries.is_root(path);
+    let follow = options.symlinks == SymlinkPolicy::Follow || is_root;
+    let metadata = if link_target.is_some() && follow {
+        fs::metadata(path).unwrap_or(link_metadata)
+    } else {
+        link_metadata
+    };
+
+    let name = path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .to_string();
+    
+    let path_str = path.to_string_lossy().to_string();
+    let mtime = mtime_ns(&metadata);
+    let ctime = ctime_ns(&metadata);
+
+    let kind = if link_target.is_some() {
+        NodeKind::Symlink
+    } else if metadata.is_dir() {
+        NodeKind::Directory
+    } else {
+        NodeKind::File
+    };
+
+    if !metadata.is_dir() {
+        // Only the first link to reach an inode gets its bytes; rayon decides which one that is.
+        // Followed symlinks can reach any file a second time, so then every file is tracked.
+        let inode = hard_link_key(&metadata, options.symlinks == SymlinkPolicy::Follow);
+        let hard_link_duplicate = inode.is_some_and(|key| state.inodes.is_repeat(key));
+        let (apparent_size, allocated_size) = if hard_link_duplicate {
+            (0, 0)
+        } else {
+            (metadata.len(), allocated_size(&metadata))
+        };
+        let size = match options.size_mode {
+            SizeMode::Apparent => apparent_size,
+            SizeMode::Allocated => allocated_size,
+        };
+        // A link that was not followed is not a file of its own
+        let file_count = if metadata.file_type().is_symlink() { 0 } else { 1 };
+        state.record_file(path, file_count, size, allocated_size);
+
+        return Ok(FileInfo {
+            name,
+            path: path_str,
+            size,
+            apparent_size,
+            allocated_size,
+            is_dir: false,
+            kind,
+            file_count,
+            child_count: 0,
+            hard_link_duplicate,
+            link_target,
+            mtime,
+            ctime,
+            inode,
+            children: Vec::new(),
+        });
+    }
+
+    // A cancelled scan keeps whatever it has counted so far, so stop before reading more entries
+    if state.is_cancelled() {
+        return Ok(FileInfo {
+            name,
+            path: path_str,
+            size: 0,
+            apparent_size: 0,
+            allocated_size: 0,
+            is_dir: true,
+            kind,
+            file_count: 0,
+            child_count: 0,
+            hard_link_duplicate: false,
+            link_target,
+            mtime,
+            ctime,
+            inode: None,
+            children: Vec::new(),
+        });
+    }
+
+    if state.boundaries.stops_at(path, &metadata) {
+        return Ok(FileInfo {
+            name,
+            path: path_str,
+            size: 0,
+            apparent_size: 0,
+            allocated_size: 0,
+            is_dir: true,
+            kind: NodeKind::MountPoint,
+            file_count: 0,
+            child_count: 0,
+            hard_link_duplicate: false,
+            link_target,
+            mtime,
+            ctime,
+            inode: None,
+            children: Vec::new(),
+        });
+    }
+
+    // With symlinks followed the same directory can be reached twice, or from inside itself.
+    // Only the first visit descends, which both breaks cycles and avoids double counting.
+    if options.symlinks == SymlinkPolicy::Follow {
+        if let Some(key) = device_inode(&metadata) {
+            if !state.inodes.directories.lock().unwrap().insert(key) {
+                return Ok(FileInfo {
+                    name,
+                    path: path_str,
+                    size: 0,
+                    apparent_size: 0,
+                    allocated_size: 0,
+                    is_dir: true,
+                    kind,
+                    file_count: 0,
+                    child_count: 0,
+                    hard_link_duplicate: true,
+                    link_target,
+                    mtime,
+                    ctime,
+                    inode: None,
+                    children: Vec::new(),
+                });
+            }
+        }
+    }
+
+    // Unchanged mtime and ctime mean nothing was added, removed or renamed here since the
+    // baseline, so its listing is reused without a read_dir. Every entry is still visited:
+    // files can be rewritten in place and changes deeper down don't touch this directory.
+    // Empty baseline directories are read again in case the first read had failed.
+    let reusable = previous.filter(|prev| {
+        prev.is_dir && prev.child_count > 0 && prev.mtime.is_some() && prev.mtime == mtime && prev.ctime == ctime
+    });
+    if let Some(prev) = reusable {
+        state.record_directory(&metadata);
+        state.reused_dirs.fetch_add(1, Ordering::Relaxed);
+
+        let children: Vec<FileInfo> = prev
+            .children
+            .par_iter()
+            .filter_map(|cached| {
+                if state.is_cancelled() {
+                    return None;
+                }
+                scan_directory_parallel(Path::new(&cached.path), Some(cached), state).ok()
+            })
+            .collect();
+
+        return Ok(directory_node(name, path_str, kind, link_target, mtime, ctime, children));
+    }
+
+    // Directory processing - much faster approach
+    let entries: Vec<PathBuf> = match fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(|e| e.map_err(|e| state.errors.record(path, &e)).ok())
+            .filter(|e| !state.filter.skips(e))
+            .map(|e| e.path())
+            .collect(),
+        Err(e) => {
+            state.errors.record(path, &e);
+            state.record_directory(&metadata);
+            return Ok(FileInfo {
+                name,
+                path: path_str,
+                size: 0,
+                apparent_size: 0,
+                allocated_size: 0,
+                is_dir: true,
+                kind,
+                file_count: 0,
+                child_count: 0,
+                hard_link_duplicate: false,
+                link_target,
+                mtime,
+                ctime,
+                inode: None,
+                children: Vec::new(),
+            });
+        }
+    };
+
+    state.record_directory(&metadata);
+    if previous.is_some() {
+        state.rescanned_dirs.fetch_add(1, Ordering::Relaxed);
+    }
+    if is_root {
+        state.estimate.top_level_total.store(entries.len(), Ordering::Relaxed);
+    }
+
+    let previous_children: HashMap<&str, &FileInfo> = previous
+        .map(|prev| prev.children.iter().map(|child| (child.name.as_str(), child)).collect())
+        .unwrap_or_default();
+
+    // Process entries in parallel - this is where the speed comes from!
+    let children: Vec<FileInfo> = entries
+        .par_iter() // Parallel iterator!
+        .filter_map(|child_path| {
+            if state.is_cancelled() {
+                return None;
+            }
+            let previous_child = child_path
+                .file_name()
+                .and_then(|name| previous_children.get(name.to_string_lossy().as_ref()).copied());
+            let child = scan_directory_parallel(child_path, previous_child, state).ok();
+            if is_root {
+                state.estimate.top_level_done.fetch_add(1, Ordering::Relaxed);
+            }
+            child
+        })
+        .collect();
+
+    Ok(directory_node(name, path_str, kind, link_target, mtime, ctime, children))
+}
+
+/// Builds a directory node from its scanned children, which it sorts largest first; trimming
+/// for display happens later, once the totals are known.
+pub(crate) fn directory_node(
+    name: String,
+    path: String,
+    kind: NodeKind,
+    link_target: Option<String>,
+    mtime: Option<i64>,
+    ctime: Option<i64>,
+    mut children: Vec<FileInfo>,
+) -> FileInfo {
+    children.sort_by_key(|child| Reverse(child.size));
+
+    FileInfo {
+        name,
+        path,
+        size: children.iter().map(|child| child.size).sum(),
+        apparent_size: children.iter().map(|child| child.apparent_size).sum(),
+        allocated_size: children.iter().map(|child| child.allocated_size).sum(),
+        is_dir: true,
+        kind,
+        file_count: children.iter().map(|child| child.file_count).sum(),
+        child_count: children.len(),
+        hard_link_duplicate: false,
+        link_target,
+        mtime,
+        ctime,
+        inode: None,
+        children,
+    }
+}
+
+fn mtime_ns(metadata: &fs::Metadata) -> Option<i64> {
+    let since_epoch = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    i64::try_from(since_epoch.as_nanos()).ok()
+}
+
+#[cfg(unix)]
+fn ctime_ns(metadata: &fs::Metadata) -> Option<i64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ctime() * 1_000_000_000 + metadata.ctime_nsec())
+}
+
+#[cfg(not(unix))]
+fn ctime_ns(_metadata: &fs::Metadata) -> Option<i64> {
+    None
+}
+
+/// Bytes actually reserved on disk. Sparse files come out smaller than their length, small
+/// files larger because of block rounding.
+#[cfg(unix)]
+pub(crate) fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    // st_blocks is always in 512-byte units, regardless of the filesystem block size
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+pub(crate) fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+/// Filesystem types that only expose kernel state; their sizes are meaningless and reading
+/// some of them blocks or never ends.
+#[cfg(target_os = "linux")]
+const PSEUDO_FILESYSTEMS: &[&str] = &[
+    "proc", "sysfs", "devtmpfs", "devpts", "securityfs", "cgroup", "cgroup2", "pstore",
+    "debugfs", "tracefs", "configfs", "fusectl", "mqueue", "hugetlbfs", "bpf",
+    "binfmt_misc", "autofs", "efivarfs", "rpc_pipefs", "nsfs",
+];
+
+/// Places a scan must not descend into: pseudo-filesystem mounts always, and any other
+/// device than the root's when `one_file_system` is set.
+struct MountBoundaries {
+    root: PathBuf,
+    root_device: Option<u64>,
+    pseudo_mounts: HashSet<PathBuf>,
+}
+
+impl MountBoundaries {
+    fn new(root: &Path, one_file_system: bool) -> Self {
+        let root_device = if one_file_system {
+            fs::metadata(root).ok().and_then(|meta| device_id(&meta))
+        } else {
+            None
+        };
+
+        Self {
+            root: root.to_path_buf(),
+            root_device,
+            pseudo_mounts: pseudo_filesystem_mounts(),
+        }
+    }
+
+    fn is_root(&self, path: &Path) -> bool {
+        path == self.root
+    }
+
+    fn stops_at(&self, path: &Path, metadata: &fs::Metadata) -> bool {
+        // Whatever the user explicitly asked to scan is always scanned
+        if self.is_root(path) {
+            return false;
+        }
+        if self.pseudo_mounts.contains(path) {
+            return true;
+        }
+        match (self.root_device, device_id(metadata)) {
+            (Some(root_device), Some(device)) => device != root_device,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn device_id(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device_id(_metadata: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// Mount points of pseudo-filesystems, read from the kernel's mount table.
+#[cfg(target_os = "linux")]
+fn pseudo_filesystem_mounts() -> HashSet<PathBuf> {
+    mount_table()
+        .into_iter()
+        .filter(|(_, fs_type)| PSEUDO_FILESYSTEMS.contains(&fs_type.as_str()))
+        .map(|(mount_point, _)| mount_point)
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pseudo_filesystem_mounts() -> HashSet<PathBuf> {
+    HashSet::new()
+}
+
+/// Every current mount point.
+#[cfg(target_os = "linux")]
+pub(crate) fn mount_points() -> Vec<PathBuf> {
+    mount_table().into_iter().map(|(mount_point, _)| mount_point).collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn mount_points() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+/// Mount points with their filesystem types, from the kernel's mount table.
+#[cfg(target_os = "linux")]
+fn mount_table() -> Vec<(PathBuf, String)> {
+    let mounts = match fs::read_to_string("/proc/self/mounts") {
+        Ok(mounts) => mounts,
+        Err(_) => return Vec::new(),
+    };
+
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = fields.nth(1)?;
+            let fs_type = fields.next()?;
+            Some((PathBuf::from(unescape_mount_path(mount_point)), fs_type.to_string()))
+        })
+        .collect()
+}
+
+/// The mount table escapes whitespace and backslashes in paths as three-digit octal (`\040`).
+#[cfg(target_os = "linux")]
+fn unescape_mount_path(raw: &str) -> String {
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'\\' && i + 3 < bytes.len())
+            .then(|| std::str::from_utf8(&bytes[i + 1..i + 4]).ok())
+            .flatten()
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Failures collected during a scan: counted by kind without limit, kept in full up to
+/// `MAX_REPORTED_ERRORS`.
+#[derive(Default)]
+struct ErrorLog {
+    /// Running count that progress events can read without taking the locks
+    total: AtomicUsize,
+    reported: Mutex<Vec<ScanError>>,
+    counts: Mutex<HashMap<ScanErrorKind, usize>>,
+}
+
+impl ErrorLog {
+    fn record(&self, path: &Path, error: &std::io::Error) {
+        let kind = ScanErrorKind::from(error.kind());
+        self.total.fetch_add(1, Ordering::Relaxed);
+        *self.counts.lock().unwrap().entry(kind).or_insert(0) += 1;
+
+        let mut reported = self.reported.lock().unwrap();
+        if reported.len() < MAX_REPORTED_ERRORS {
+            reported.push(ScanError {
+                path: path.to_string_lossy().to_string(),
+                kind,
+                message: error.to_string(),
+            });
+        }
+    }
+
+    /// Total number of failures and their breakdown by kind, largest group first.
+    fn groups(&self) -> (usize, Vec<ScanErrorGroup>) {
+        let counts = self.counts.lock().unwrap();
+        let mut groups: Vec<ScanErrorGroup> = counts
+            .iter()
+            .map(|(kind, count)| ScanErrorGroup { kind: *kind, count: *count })
+            .collect();
+        groups.sort_by_key(|group| Reverse(group.count));
+        (counts.values().sum(), groups)
+    }
+}
+
+/// Exclude globs and the include-only extension list, checked against each directory entry
+/// before it is stat'ed or read.
+pub(crate) struct ScanFilter {
+    name_patterns: GlobSet,
+    path_patterns: GlobSet,
+    include_extensions: Vec<String>,
+    excluded_entries: AtomicUsize,
+    excluded_bytes: AtomicU64,
+}
+
+impl ScanFilter {
+    pub(crate) fn new(options: &ScanOptions) -> Result<Self, globset::Error> {
+        let mut name_patterns = GlobSetBuilder::new();
+        let mut path_patterns = GlobSetBuilder::new();
+        for pattern in &options.exclude {
+            if pattern.contains('/') {
+                let glob = GlobBuilder::new(pattern).literal_separator(true).build()?;
+                path_patterns.add(glob);
+            } else {
+                name_patterns.add(Glob::new(pattern)?);
+            }
+        }
+
+        Ok(Self {
+            name_patterns: name_patterns.build()?,
+            path_patterns: path_patterns.build()?,
+            include_extensions: options
+                .include_extensions
+                .iter()
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .collect(),
+            excluded_entries: AtomicUsize::new(0),
+            excluded_bytes: AtomicU64::new(0),
+        })
+    }
+
+    /// Decides whether an entry is left out of the scan, and counts it if so. Only the entry's
+    /// type from `read_dir` is used for directories; files get one extra lstat for their size.
+    fn skips(&self, entry: &fs::DirEntry) -> bool {
+        let path = entry.path();
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        if !self.excludes(&path, is_dir) {
+            return false;
+        }
+
+        self.excluded_entries.fetch_add(1, Ordering::Relaxed);
+        if !is_dir {
+            if let Ok(metadata) = fs::symlink_metadata(&path) {
+                self.excluded_bytes.fetch_add(metadata.len(), Ordering::Relaxed);
+            }
+        }
+        true
+    }
+
+    pub(crate) fn excludes(&self, path: &Path, is_dir: bool) -> bool {
+        path.file_name().is_some_and(|name| self.name_patterns.is_match(name))
+            || self.path_patterns.is_match(path)
+            || (!is_dir && !self.includes_extension(path))
+    }
+
+    fn includes_extension(&self, path: &Path) -> bool {
+        if self.include_extensions.is_empty() {
+            return true;
+        }
+        path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .is_some_and(|ext| self.include_extensions.contains(&ext))
+    }
+}
+
+/// Inodes already seen by a scan: files by how many links reached them, and directories
+/// entered while following symlinks.
+#[derive(Default)]
+struct InodeTracker<'a> {
+    files: Mutex<HashMap<(u64, u64), usize>>,
+    directories: Mutex<HashSet<(u64, u64)>>,
+    /// Files the rest of the tree already counts, when only part of it is scanned again
+    counted_elsewhere: Option<&'a HashSet<(u64, u64)>>,
+}
+
+impl InodeTracker<'_> {
+    /// Counts one more link reaching the file `key`; true when it was seen before.
+    fn is_repeat(&self, key: (u64, u64)) -> bool {
+        let mut seen = self.files.lock().unwrap();
+        let links = seen.entry(key).or_insert(0);
+        *links += 1;
+        *links > 1 || self.counted_elsewhere.is_some_and(|counted| counted.contains(&key))
+    }
+}
+
+#[cfg(unix)]
+fn device_inode(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn device_inode(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// (device, inode) of a file that could be reached more than once. Without `track_all` that
+/// means more than one hard link; files with a single link are never tracked.
+#[cfg(unix)]
+fn hard_link_key(metadata: &fs::Metadata, track_all: bool) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    if track_all || metadata.nlink() > 1 {
+        device_inode(metadata)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn hard_link_key(_metadata: &fs::Metadata, _track_all: bool) -> Option<(u64, u64)> {
+    None
+}
+
+/// Copies a node without its children; `child_count` still tells the frontend there is more to fetch.
+pub fn shallow_copy(node: &FileInfo) -> FileInfo {
+    FileInfo {
+        name: node.name.clone(),
+        path: node.path.clone(),
+        size: node.size,
+        apparent_size: node.apparent_size,
+        allocated_size: node.allocated_size,
+        is_dir: node.is_dir,
+        kind: node.kind,
+        file_count: node.file_count,
+        child_count: node.child_count,
+        hard_link_duplicate: node.hard_link_duplicate,
+        link_target: node.link_target.clone(),
+        mtime: node.mtime,
+        ctime: node.ctime,
+        inode: node.inode,
+        children: Vec::new(),
+    }
+}
+
+/// Builds the copy of a tree sent to the frontend: `depth` levels of children, each directory
+/// keeping its `limit` largest entries and folding the rest into a single "N other items" node
+/// so sizes and counts still add up to the parent's.
+pub fn display_view(node: &FileInfo, depth: usize, limit: usize) -> FileInfo {
+    let mut view = shallow_copy(node);
+    if depth == 0 {
+        return view;
+    }
+
+    let shown = if limit > 0 { limit.min(node.children.len()) } else { node.children.len() };
+    view.children = node.children[..shown]
+        .iter()
+        .map(|child| display_view(child, depth - 1, limit))
+        .collect();
+
+    let folded = &node.children[shown..];
+    if !folded.is_empty() {
+        view.children.push(FileInfo {
+            name: format!("{} other items", folded.len()),
+            path: node.path.clone(),
+            size: folded.iter().map(|child| child.size).sum(),
+            apparent_size: folded.iter().map(|child| child.apparent_size).sum(),
+            allocated_size: folded.iter().map(|child| child.allocated_size).sum(),
+            is_dir: false,
+            kind: NodeKind::Other,
+            file_count: folded.iter().map(|child| child.file_count).sum(),
+            child_count: 0,
+            hard_link_duplicate: false,
+            link_target: None,
+            mtime: None,
+            ctime: None,
+            inode: None,
+            children: Vec::new(),
+        });
+    }
+
+    view
+}
+
+/// Puts `replacement` at `path`, or removes whatever is there when it is `None`, then
+/// recomputes every ancestor. Returns false when `path` is the root or its parent isn't in
+/// the tree (excluded, or beyond a mount boundary).
+pub fn replace_node(root: &mut FileInfo, path: &Path, replacement: Option<FileInfo>) -> bool {
+    let Ok(relative) = path.strip_prefix(&root.path) else {
+        return false;
+    };
+    let components: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect();
+    if components.is_empty() {
+        return false;
+    }
+    replace_in(root, &components, replacement)
+}
+
+fn replace_in(node: &mut FileInfo, components: &[String], replacement: Option<FileInfo>) -> bool {
+    let (name, rest) = components
+        .split_first()
+        .expect("replace_in needs a path component");
+    let position = node.children.iter().position(|child| &child.name == name);
+
+    // The child is taken out and put back where its new size belongs: the others stay sorted
+    // and the totals only move by its change, however many children there are
+    let old = position.map(|index| node.children.remove(index));
+    let removed = old.as_ref().map(Totals::of).unwrap_or_default();
+    let (changed, new) = if rest.is_empty() {
+        (old.is_some() || replacement.is_some(), replacement)
+    } else {
+        match old {
+            Some(mut child) => (replace_in(&mut child, rest, replacement), Some(child)),
+            None => (false, None),
+        }
+    };
+
+    if !changed {
+        if let (Some(index), Some(child)) = (position, new) {
+            node.children.insert(index, child);
+        }
+        return false;
+    }
+
+    let added = new.as_ref().map(Totals::of).unwrap_or_default();
+    if let Some(child) = new {
+        let index = node.children.partition_point(|other| other.size >= child.size);
+        node.children.insert(index, child);
+    }
+    node.size = node.size + added.size - removed.size;
+    node.apparent_size = node.apparent_size + added.apparent_size - removed.apparent_size;
+    node.allocated_size = node.allocated_size + added.allocated_size - removed.allocated_size;
+    node.file_count = node.file_count + added.file_count - removed.file_count;
+    node.child_count = node.children.len();
+    true
+}
+
+/// The sums a directory keeps over its children.
+#[derive(Default)]
+struct Totals {
+    size: u64,
+    apparent_size: u64,
+    allocated_size: u64,
+    file_count: usize,
+}
+
+impl Totals {
+    fn of(node: &FileInfo) -> Self {
+        Totals {
+            size: node.size,
+            apparent_size: node.apparent_size,
+            allocated_size: node.allocated_size,
+            file_count: node.file_count,
+        }
+    }
+}
+
+/// Walks from the scan root down to `path`, one component at a time.
+pub fn find_node<'a>(root: &'a FileInfo, path: &Path) -> Option<&'a FileInfo> {
+    let relative = path.strip_prefix(&root.path).ok()?;
+    let mut node = root;
+    for component in relative.components() {
+        let name = component.as_os_str().to_string_lossy();
+        node = node.children.iter().find(|child| child.name == name)?;
+    }
+    Some(node)
+}
+
+/// Orders a directory's children for a view. Stored trees stay sorted by size; other orders
+/// are applied to the borrowed list each time it is shown.
+pub fn sort_children(children: &mut [&FileInfo], key: SortKey) {
+    match key {
+        SortKey::Size => children.sort_by_key(|child| Reverse(child.size)),
+        SortKey::Name => children.sort_by(|a, b| a.name.cmp(&b.name)),
+        SortKey::Count => children.sort_by_key(|child| Reverse(child.file_count)),
+        SortKey::Mtime => children.sort_by_key(|child| Reverse(child.mtime)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn apparent() -> ScanOptions {
+        ScanOptions {
+            size_mode: SizeMode::Apparent,
+            ..ScanOptions::default()
+        }
+    }
+
+    fn scan(root: &Path, options: &ScanOptions, previous: Option<&FileInfo>) -> ScanResult {
+        scan_tree(root, 0, options, previous, &AtomicBool::new(false), &NoProgress).unwrap()
+    }
+
+    #[test]
+    fn incremental_scans_reuse_listings_but_see_files_rewritten_in_place() {
+        let scratch = TempDir::new().unwrap();
+        fs::create_dir(scratch.path().join("sub")).unwrap();
+        fs::write(scratch.path().join("sub/grows"), b"abc").unwrap();
+        fs::write(scratch.path().join("same"), b"abcd").unwrap();
+        let options = apparent();
+        let baseline = scan(scratch.path(), &options, None);
+        assert_eq!(baseline.total_size, 7);
+
+        // Rewriting a file leaves its directory's timestamps alone
+        fs::write(scratch.path().join("sub/grows"), b"abcdefghij").unwrap();
+        let rescanned = scan(scratch.path(), &options, Some(&baseline.root));
+
+        assert_eq!(rescanned.reused_directories, 2);
+        assert_eq!(rescanned.rescanned_directories, 0);
+        assert_eq!(rescanned.total_size, 14);
+        assert_eq!(rescanned.file_count, 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hard_links_are_counted_once() {
+        let scratch = TempDir::new().unwrap();
+        fs::create_dir(scratch.path().join("a")).unwrap();
+        fs::create_dir(scratch.path().join("b")).unwrap();
+        fs::write(scratch.path().join("a/data"), vec![0u8; 1000]).unwrap();
+        fs::hard_link(scratch.path().join("a/data"), scratch.path().join("b/data")).unwrap();
+
+        let options = apparent();
+        let result = scan(scratch.path(), &options, None);
+        assert_eq!(result.total_size, 1000);
+        assert_eq!(result.hard_link_duplicates, 1);
+
+        // Reused listings still count the inode once, whichever link comes first
+        let rescanned = scan(scratch.path(), &options, Some(&result.root));
+        assert_eq!(rescanned.reused_directories, 3);
+        assert_eq!(rescanned.total_size, 1000);
+        assert_eq!(rescanned.hard_link_duplicates, 1);
+    }
+
+    #[test]
+    fn excluded_entries_are_skipped_and_counted() {
+        let scratch = TempDir::new().unwrap();
+        fs::create_dir_all(scratch.path().join("project/node_modules/dep")).unwrap();
+        fs::write(scratch.path().join("project/node_modules/dep/index.js"), b"x").unwrap();
+        fs::write(scratch.path().join("project/main.rs"), b"fn main() {}").unwrap();
+        fs::write(scratch.path().join("project/build.log"), b"lots of output").unwrap();
+
+        let options = ScanOptions {
+            exclude: vec!["*.log".to_string(), "**/project/node_modules".to_string()],
+            ..apparent()
+        };
+        let result = scan(scratch.path(), &options, None);
+
+        let project = &result.root.children[0];
+        let names: Vec<&str> = project.children.iter().map(|child| child.name.as_str()).collect();
+        assert_eq!(names, ["main.rs"]);
+        assert_eq!(result.total_size, 12);
+        assert_eq!(result.excluded_entries, 2);
+        // Excluded directories are never read, so only the log's bytes are known
+        assert_eq!(result.excluded_bytes, 14);
+        assert_eq!(result.unreadable_bytes_estimate, None);
+    }
+
+    #[test]
+    fn replaced_entries_move_to_their_place_and_update_the_totals() {
+        let scratch = TempDir::new().unwrap();
+        fs::create_dir(scratch.path().join("sub")).unwrap();
+        fs::write(scratch.path().join("sub/small"), b"a").unwrap();
+        fs::write(scratch.path().join("sub/large"), b"abcde").unwrap();
+        fs::write(scratch.path().join("middle"), b"abc").unwrap();
+        let options = apparent();
+        let mut tree = scan(scratch.path(), &options, None).root;
+        let root = PathBuf::from(&tree.path);
+
+        fs::write(root.join("sub/small"), b"abcdefghij").unwrap();
+        fs::create_dir(root.join("sub/new")).unwrap();
+        fs::write(root.join("sub/new/file"), b"ab").unwrap();
+        let counted = counted_inodes(&tree);
+        let scanner = EntryScanner::new(&root, &options, &counted).unwrap();
+        for path in [root.join("sub/small"), root.join("sub/new")] {
+            assert!(replace_node(&mut tree, &path, scanner.scan(&path)));
+        }
+        assert!(replace_node(&mut tree, &root.join("middle"), None));
+
+        let sub = &tree.children[0];
+        let names: Vec<&str> = sub.children.iter().map(|child| child.name.as_str()).collect();
+        assert_eq!(names, ["small", "large", "new"]);
+        assert_eq!((sub.size, sub.file_count, sub.child_count), (17, 3, 3));
+        assert_eq!((tree.size, tree.file_count, tree.child_count), (17, 3, 1));
+        assert_eq!(directory_count(&tree), 3);
+    }
+}
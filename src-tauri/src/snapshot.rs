@@ -0,0 +1,171 @@
+//! Scan snapshots saved under the app data directory.
+//!
+//! Each snapshot is one `<id>.snap` file: a magic/version tag, the bincode-encoded
+//! `SnapshotMeta` that `list` needs, then the full `ScanResult` (untrimmed tree included),
+//! bincode-encoded inside a gzip stream. Listing only reads the uncompressed header.
+
+use std::cmp::Reverse;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use crate::scanner::{ScanResult, SizeMode};
+
+/// Bumped whenever the layout of `ScanResult` changes in a way bincode can't read back.
+const MAGIC: &[u8; 8] = b"DASNAP05";
+const EXTENSION: &str = "snap";
+/// Snapshots saved within the same millisecond before giving up on a free ID
+const MAX_ID_ATTEMPTS: u32 = 100;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotMeta {
+    pub id: String,
+    pub name: String,
+    pub root_path: String,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+    pub total_size: u64,
+    pub file_count: usize,
+    pub size_mode: SizeMode,
+}
+
+/// Writes `result` to a new snapshot file. The file only appears under its final name once
+/// it is complete, so a crash never leaves a truncated snapshot behind.
+pub fn save(dir: &Path, name: Option<String>, result: &ScanResult) -> io::Result<SnapshotMeta> {
+    fs::create_dir_all(dir)?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let (id, temp_path, file) = reserve_id(dir, now.as_millis())?;
+    let meta = SnapshotMeta {
+        id,
+        name: name.unwrap_or_else(|| result.root.path.clone()),
+        root_path: result.root.path.clone(),
+        created_at: now.as_secs(),
+        total_size: result.total_size,
+        file_count: result.file_count,
+        size_mode: result.size_mode,
+    };
+
+    let final_path = snapshot_path(dir, &meta.id)?;
+
+    let mut writer = BufWriter::new(file);
+    writer.write_all(MAGIC)?;
+    bincode::serialize_into(&mut writer, &meta).map_err(invalid_data)?;
+
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+    bincode::serialize_into(&mut encoder, result).map_err(invalid_data)?;
+    encoder.finish()?.flush()?;
+
+    fs::rename(&temp_path, &final_path)?;
+    Ok(meta)
+}
+
+/// Picks the ID for a snapshot saved at `millis`: the time itself, or `<millis>-2` and so on
+/// when another snapshot was saved in the same millisecond. The ID's temporary file is
+/// created here, which reserves it: nobody else gets that ID until the file is renamed to
+/// its final name, after which it shows up as taken.
+fn reserve_id(dir: &Path, millis: u128) -> io::Result<(String, PathBuf, File)> {
+    for attempt in 1..=MAX_ID_ATTEMPTS {
+        let id = match attempt {
+            1 => millis.to_string(),
+            _ => format!("{}-{}", millis, attempt),
+        };
+        let final_path = snapshot_path(dir, &id)?;
+        let temp_path = final_path.with_extension("snap.tmp");
+        match OpenOptions::new().write(true).create_new(true).open(&temp_path) {
+            Ok(_) if fs::symlink_metadata(&final_path).is_ok() => {
+                fs::remove_file(&temp_path)?;
+            }
+            Ok(file) => return Ok((id, temp_path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("No free snapshot id for {}", millis),
+    ))
+}
+
+/// Every readable snapshot in `dir`, newest first. Files that fail to parse are skipped.
+pub fn list(dir: &Path) -> io::Result<Vec<SnapshotMeta>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut snapshots: Vec<SnapshotMeta> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == EXTENSION))
+        .filter_map(|path| read_header(&mut BufReader::new(File::open(path).ok()?)).ok())
+        .collect();
+
+    snapshots.sort_by_key(|snapshot| Reverse(snapshot.created_at));
+    Ok(snapshots)
+}
+
+pub fn load(dir: &Path, id: &str) -> io::Result<(SnapshotMeta, ScanResult)> {
+    let mut reader = BufReader::new(File::open(snapshot_path(dir, id)?)?);
+    let meta = read_header(&mut reader)?;
+    let result = bincode::deserialize_from(GzDecoder::new(reader)).map_err(invalid_data)?;
+    Ok((meta, result))
+}
+
+fn read_header(reader: &mut impl Read) -> io::Result<SnapshotMeta> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Not a snapshot file, or from an incompatible version",
+        ));
+    }
+    bincode::deserialize_from(reader).map_err(invalid_data)
+}
+
+/// IDs come from the frontend, so only the characters `save` generates are accepted.
+fn snapshot_path(dir: &Path, id: &str) -> io::Result<PathBuf> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid snapshot id: {}", id),
+        ));
+    }
+    Ok(dir.join(format!("{}.{}", id, EXTENSION)))
+}
+
+fn invalid_data(error: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn saves_in_the_same_millisecond_get_their_own_ids() {
+        let scratch = TempDir::new().unwrap();
+        let dir = scratch.path();
+
+        let (first, first_temp, _) = reserve_id(dir, 1_700_000_000_000).unwrap();
+        let (second, _, _) = reserve_id(dir, 1_700_000_000_000).unwrap();
+        assert_eq!(first, "1700000000000");
+        assert_eq!(second, "1700000000000-2");
+
+        // Once the first is finished its ID stays taken
+        fs::rename(&first_temp, snapshot_path(dir, &first).unwrap()).unwrap();
+        let (third, _, _) = reserve_id(dir, 1_700_000_000_000).unwrap();
+        assert_eq!(third, "1700000000000-3");
+    }
+}
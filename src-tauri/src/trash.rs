@@ -0,0 +1,409 @@
+//! Moving items to the Trash, so a deletion from the app can be undone from any file manager.
+//!
+//! Elsewhere than on macOS this follows the freedesktop.org Trash specification: items on the
+//! home filesystem go to `$XDG_DATA_HOME/Trash`, items on other mounts to
+//! `$topdir/.Trash/$uid` when the admin has set that up, otherwise `$topdir/.Trash-$uid`.
+//! Each trashed item gets a `.trashinfo` file recording where it came from and when.
+//!
+//! On macOS items go where Finder keeps its Trash: `~/.Trash` for the home volume,
+//! `<volume>/.Trashes/$uid` for others. Finder's "Put Back" only knows about what it trashed
+//! itself, but the items show up in the Trash and the app's own undo works.
+//!
+//! Items are only ever renamed, never copied across filesystems.
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Gives up on finding a free name in the trash after this many numbered attempts.
+#[cfg(unix)]
+const MAX_NAME_ATTEMPTS: u32 = 10_000;
+
+/// Where an item ended up in the trash.
+#[derive(Debug, Clone)]
+pub struct TrashedItem {
+    pub original: PathBuf,
+    /// The item itself, under `<trash>/files`
+    pub trashed: PathBuf,
+    /// Its `.trashinfo` file, under `<trash>/info`; macOS has none
+    pub info_file: Option<PathBuf>,
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn move_to_trash(path: &Path) -> io::Result<TrashedItem> {
+    use std::os::unix::fs::MetadataExt;
+
+    let original = absolute_path(path)?;
+    let device = fs::symlink_metadata(&original)?.dev();
+    let (trash_dir, topdir) = trash_dir_for(&original, device)?;
+
+    let files_dir = trash_dir.join("files");
+    let info_dir = trash_dir.join("info");
+    create_private_dir(&files_dir)?;
+    create_private_dir(&info_dir)?;
+
+    // Trash directories on other mounts record paths relative to the mount, so they stay
+    // valid if it is mounted elsewhere later
+    let recorded = match &topdir {
+        Some(top) => original.strip_prefix(top).unwrap_or(&original),
+        None => &original,
+    };
+    let file_name = original.file_name().expect("absolute_path keeps the file name");
+    let (name, mut info, info_file) = reserve_name(&files_dir, &info_dir, file_name)?;
+
+    let contents = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        encode_path(recorded),
+        deletion_date()
+    );
+    let trashed = files_dir.join(&name);
+    let moved = info
+        .write_all(contents.as_bytes())
+        .and_then(|_| info.sync_all())
+        .and_then(|_| fs::rename(&original, &trashed));
+    if let Err(e) = moved {
+        let _ = fs::remove_file(&info_file);
+        return Err(e);
+    }
+
+    Ok(TrashedItem { original, trashed, info_file: Some(info_file) })
+}
+
+#[cfg(target_os = "macos")]
+pub fn move_to_trash(path: &Path) -> io::Result<TrashedItem> {
+    use std::os::unix::fs::MetadataExt;
+
+    let original = absolute_path(path)?;
+    let device = fs::symlink_metadata(&original)?.dev();
+    let home_trash = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".Trash"));
+    let trash_dir = match home_trash {
+        Some(home_trash) if nearest_device(&home_trash) == Some(device) => home_trash,
+        _ => {
+            // SAFETY: getuid has no preconditions and cannot fail
+            let uid = unsafe { libc::getuid() };
+            mount_top(&original, device).join(".Trashes").join(uid.to_string())
+        }
+    };
+    create_private_dir(&trash_dir)?;
+
+    let file_name = original.file_name().expect("absolute_path keeps the file name");
+    let stem = Path::new(file_name).file_stem().unwrap_or(file_name).to_owned();
+    let extension = Path::new(file_name).extension().map(|ext| ext.to_owned());
+    for attempt in 1..=MAX_NAME_ATTEMPTS {
+        // Finder's own style for clashes: `name 2.ext`
+        let mut name = stem.clone();
+        if attempt > 1 {
+            name.push(format!(" {}", attempt));
+        }
+        if let Some(extension) = &extension {
+            name.push(".");
+            name.push(extension);
+        }
+        let trashed = trash_dir.join(&name);
+        match rename_exclusive(&original, &trashed) {
+            Ok(()) => return Ok(TrashedItem { original, trashed, info_file: None }),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("No free name in the trash for {}", file_name.to_string_lossy()),
+    ))
+}
+
+/// Renames `from` to `to`, failing with `AlreadyExists` instead of replacing whatever is
+/// already at `to`, even something that appeared a moment ago.
+#[cfg(target_os = "macos")]
+fn rename_exclusive(from: &Path, to: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_from = CString::new(from.as_os_str().as_bytes())?;
+    let c_to = CString::new(to.as_os_str().as_bytes())?;
+    // SAFETY: both paths are NUL-terminated and renamex_np only reads them
+    if unsafe { libc::renamex_np(c_from.as_ptr(), c_to.as_ptr(), libc::RENAME_EXCL) } == 0 {
+        return Ok(());
+    }
+    match io::Error::last_os_error() {
+        // Filesystems that can't rename exclusively
+        e if e.raw_os_error() == Some(libc::ENOTSUP) => rename_without_replacing(from, to),
+        e => Err(e),
+    }
+}
+
+/// Renames `from` to `to`, failing with `AlreadyExists` instead of replacing whatever is
+/// already at `to`, even something that appeared a moment ago.
+#[cfg(target_os = "linux")]
+fn rename_exclusive(from: &Path, to: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_from = CString::new(from.as_os_str().as_bytes())?;
+    let c_to = CString::new(to.as_os_str().as_bytes())?;
+    // SAFETY: both paths are NUL-terminated and renameat2 only reads them
+    let renamed = unsafe {
+        libc::renameat2(libc::AT_FDCWD, c_from.as_ptr(), libc::AT_FDCWD, c_to.as_ptr(), libc::RENAME_NOREPLACE)
+    };
+    if renamed == 0 {
+        return Ok(());
+    }
+    match io::Error::last_os_error() {
+        // Filesystems, or kernels, that can't rename exclusively
+        e if matches!(e.raw_os_error(), Some(libc::EINVAL | libc::ENOSYS)) => rename_without_replacing(from, to),
+        e => Err(e),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn rename_exclusive(from: &Path, to: &Path) -> io::Result<()> {
+    rename_without_replacing(from, to)
+}
+
+/// The fallback where renames can't be told not to replace: linking fails rather than
+/// replace anything, so files are linked and then unlinked. Directories can't be linked, so
+/// for them something appearing between the check and the rename is still replaced.
+fn rename_without_replacing(from: &Path, to: &Path) -> io::Result<()> {
+    if !fs::symlink_metadata(from)?.is_dir() {
+        fs::hard_link(from, to)?;
+        return fs::remove_file(from);
+    }
+    if fs::symlink_metadata(to).is_ok() {
+        return Err(io::ErrorKind::AlreadyExists.into());
+    }
+    fs::rename(from, to)
+}
+
+#[cfg(not(unix))]
+pub fn move_to_trash(_path: &Path) -> io::Result<TrashedItem> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Moving to the trash is only supported on freedesktop systems and macOS",
+    ))
+}
+
+/// Moves a trashed item back out to `dest`, which must not exist, and drops its
+/// `.trashinfo` file. Fails with `AlreadyExists` rather than replace anything at `dest`.
+pub fn restore(trashed: &Path, dest: &Path) -> io::Result<()> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    rename_exclusive(trashed, dest).map_err(|e| match e.kind() {
+        io::ErrorKind::AlreadyExists => {
+            io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", dest.display()))
+        }
+        _ => e,
+    })?;
+    if let Some(info_file) = info_file_for(trashed) {
+        let _ = fs::remove_file(info_file);
+    }
+    Ok(())
+}
+
+/// The `.trashinfo` file belonging to an item under `<trash>/files`.
+fn info_file_for(trashed: &Path) -> Option<PathBuf> {
+    let files_dir = trashed.parent()?;
+    if files_dir.file_name()? != "files" {
+        return None;
+    }
+    let mut info_name = trashed.file_name()?.to_owned();
+    info_name.push(".trashinfo");
+    Some(files_dir.parent()?.join("info").join(info_name))
+}
+
+/// Makes `path` absolute with its parent's symlinks resolved, but not the item itself: a
+/// trashed symlink must be the link, not its target.
+#[cfg(unix)]
+fn absolute_path(path: &Path) -> io::Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Cannot trash {}", path.display())))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.canonicalize()?,
+        _ => std::env::current_dir()?,
+    };
+    Ok(parent.join(file_name))
+}
+
+/// Picks the trash directory for an item on `device`, with the mount's top directory when it
+/// isn't the home trash.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn trash_dir_for(original: &Path, device: u64) -> io::Result<(PathBuf, Option<PathBuf>)> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    if let Some(home_trash) = home_trash() {
+        if nearest_device(&home_trash) == Some(device) {
+            return Ok((home_trash, None));
+        }
+    }
+
+    let top = mount_top(original, device);
+    // SAFETY: getuid has no preconditions and cannot fail
+    let uid = unsafe { libc::getuid() };
+
+    // An admin-provided `.Trash` only counts when it is a real, sticky directory
+    let admin_trash = top.join(".Trash");
+    if let Ok(meta) = fs::symlink_metadata(&admin_trash) {
+        if meta.is_dir() && meta.permissions().mode() & 0o1000 != 0 {
+            let user_trash = admin_trash.join(uid.to_string());
+            if create_private_dir(&user_trash).is_ok() {
+                return Ok((user_trash, Some(top)));
+            }
+        }
+    }
+
+    let user_trash = top.join(format!(".Trash-{}", uid));
+    create_private_dir(&user_trash).map_err(|e| {
+        io::Error::new(e.kind(), format!("No usable trash directory on {}: {}", top.display(), e))
+    })?;
+    let meta = fs::symlink_metadata(&user_trash)?;
+    if !meta.is_dir() || meta.uid() != uid {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not a trash directory owned by this user", user_trash.display()),
+        ));
+    }
+    Ok((user_trash, Some(top)))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn home_trash() -> Option<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+    Some(data_home.join("Trash"))
+}
+
+/// Device of `path`, or of its closest existing ancestor when it doesn't exist yet.
+#[cfg(unix)]
+fn nearest_device(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    path.ancestors().find_map(|dir| fs::metadata(dir).ok()).map(|meta| meta.dev())
+}
+
+/// The highest ancestor of `path` still on `device`, i.e. the mount point holding it.
+#[cfg(unix)]
+fn mount_top(path: &Path, device: u64) -> PathBuf {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut top = path.to_path_buf();
+    for dir in path.ancestors().skip(1) {
+        match fs::metadata(dir) {
+            Ok(meta) if meta.dev() == device => top = dir.to_path_buf(),
+            _ => break,
+        }
+    }
+    top
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)
+}
+
+/// Claims a name in the trash by creating its `.trashinfo` file, which the spec makes the
+/// atomic step; a clash gets a numbered name instead.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn reserve_name(files_dir: &Path, info_dir: &Path, file_name: &std::ffi::OsStr) -> io::Result<(OsString, File, PathBuf)> {
+    for attempt in 1..=MAX_NAME_ATTEMPTS {
+        let mut name = file_name.to_owned();
+        if attempt > 1 {
+            name.push(format!(".{}", attempt));
+        }
+        let mut info_name = name.clone();
+        info_name.push(".trashinfo");
+        let info_file = info_dir.join(info_name);
+
+        match OpenOptions::new().write(true).create_new(true).open(&info_file) {
+            Ok(info) => {
+                // A leftover item without its info file still blocks the name
+                if fs::symlink_metadata(files_dir.join(&name)).is_ok() {
+                    drop(info);
+                    let _ = fs::remove_file(&info_file);
+                    continue;
+                }
+                return Ok((name, info, info_file));
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("No free name in the trash for {}", file_name.to_string_lossy()),
+    ))
+}
+
+/// Percent-encodes a path for the `Path=` key, leaving `/` and unreserved characters alone.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn encode_path(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut encoded = String::new();
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.!~*'()".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Local time in the `YYYY-MM-DDThh:mm:ss` form the spec asks for.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn deletion_date() -> String {
+    // SAFETY: time accepts a null pointer, and localtime_r only writes into the zeroed struct
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        libc::localtime_r(&now, &mut tm);
+    }
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn restore_never_replaces_what_is_at_the_destination() {
+        let scratch = TempDir::new().unwrap();
+        let trashed_file = scratch.path().join("trashed-file");
+        let trashed_dir = scratch.path().join("trashed-dir");
+        fs::write(&trashed_file, b"trashed").unwrap();
+        fs::create_dir(&trashed_dir).unwrap();
+
+        let taken_file = scratch.path().join("taken-file");
+        fs::write(&taken_file, b"new").unwrap();
+        let error = restore(&trashed_file, &taken_file).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&taken_file).unwrap(), b"new");
+        assert!(trashed_file.exists());
+
+        // A plain rename would replace an empty directory
+        let taken_dir = scratch.path().join("taken-dir");
+        fs::create_dir(&taken_dir).unwrap();
+        assert_eq!(restore(&trashed_dir, &taken_dir).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert!(trashed_dir.exists());
+
+        let free = scratch.path().join("nested").join("free");
+        restore(&trashed_file, &free).unwrap();
+        assert_eq!(fs::read(&free).unwrap(), b"trashed");
+        assert!(!trashed_file.exists());
+    }
+}
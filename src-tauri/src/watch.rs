@@ -0,0 +1,239 @@
+//! Live updates for a finished scan.
+//!
+//! A `notify` watcher feeds filesystem events into a worker thread, which batches them,
+//! re-stats the affected paths, patches the stored tree and re-propagates sizes up to the
+//! root before emitting `tree-updated`. Linux caps inotify watches per user, so trees with
+//! more directories than the budget are polled instead.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use notify::{Config, Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+#[cfg(target_os = "linux")]
+use walkdir::WalkDir;
+
+use crate::app::StoredScan;
+use crate::scanner::find_node;
+
+/// Events arriving within this window are applied together, so a build writing thousands of
+/// files produces a handful of updates rather than thousands.
+const BATCH_WINDOW: Duration = Duration::from_millis(500);
+
+/// How often the polling fallback walks the tree.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Share of the inotify limit a single watch may use, leaving room for other programs.
+const WATCH_BUDGET_PERCENT: usize = 80;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchMode {
+    /// inotify/FSEvents/ReadDirectoryChanges, depending on the platform
+    Native,
+    /// Periodic re-stat of the whole tree, used when native watches would exceed the limit
+    Polling,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WatchStatus {
+    pub scan_id: u64,
+    pub mode: WatchMode,
+    pub watched_directories: usize,
+    /// `fs.inotify.max_user_watches`, where there is such a limit
+    pub watch_limit: Option<usize>,
+}
+
+/// Payload of the `tree-updated` event.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TreeUpdate {
+    pub scan_id: u64,
+    /// Paths whose node was added, replaced or removed
+    pub paths: Vec<String>,
+    pub total_size: u64,
+    pub file_count: usize,
+    /// The platform dropped events (queue overflow), so the tree may have drifted; rescanning
+    /// is the only way to be sure again
+    pub rescan_needed: bool,
+}
+
+/// Keeps a watch running; dropping it stops the watcher, which in turn ends the worker thread.
+pub struct WatchHandle {
+    pub status: WatchStatus,
+    _watcher: Box<dyn Watcher + Send>,
+}
+
+pub fn start(
+    scan_id: u64,
+    scan: Arc<StoredScan>,
+    app_handle: AppHandle,
+) -> Result<WatchHandle, String> {
+    let (root, directories) = {
+        let result = scan.result.read().unwrap();
+        (PathBuf::from(&result.root.path), result.directory_count)
+    };
+    let watch_limit = inotify_watch_limit();
+    let within_budget = watch_limit.is_none_or(|limit| {
+        let budget = limit * WATCH_BUDGET_PERCENT / 100;
+        directories <= budget && native_watch_count(&root, budget) <= budget
+    });
+
+    let (sender, receiver) = mpsc::channel();
+    let native = if within_budget {
+        match native_watcher(&root, sender.clone()) {
+            Ok(watcher) => Some(watcher),
+            // Other programs may already hold most of the watches; fall back rather than fail
+            Err(e) if matches!(e.kind, notify::ErrorKind::MaxFilesWatch) => None,
+            Err(e) => return Err(e.to_string()),
+        }
+    } else {
+        None
+    };
+
+    let (watcher, mode): (Box<dyn Watcher + Send>, WatchMode) = match native {
+        Some(watcher) => (watcher, WatchMode::Native),
+        None => {
+            let mut watcher =
+                PollWatcher::new(sender, Config::default().with_poll_interval(POLL_INTERVAL))
+                    .map_err(|e| e.to_string())?;
+            watcher
+                .watch(&root, RecursiveMode::Recursive)
+                .map_err(|e| e.to_string())?;
+            (Box::new(watcher), WatchMode::Polling)
+        }
+    };
+
+    thread::spawn(move || apply_events(scan_id, scan, receiver, app_handle));
+
+    Ok(WatchHandle {
+        status: WatchStatus {
+            scan_id,
+            mode,
+            watched_directories: directories,
+            watch_limit,
+        },
+        _watcher: watcher,
+    })
+}
+
+fn native_watcher(
+    root: &Path,
+    sender: mpsc::Sender<notify::Result<Event>>,
+) -> notify::Result<Box<dyn Watcher + Send>> {
+    let mut watcher = RecommendedWatcher::new(sender, Config::default())?;
+    watcher.watch(root, RecursiveMode::Recursive)?;
+    Ok(Box::new(watcher))
+}
+
+/// Watches notify's inotify backend would add for `root`, counted no further than just past
+/// `budget`. It follows symlinks and crosses mounts on its way down, whatever the scan did,
+/// so this can be well above the tree's directory count.
+#[cfg(target_os = "linux")]
+fn native_watch_count(root: &Path, budget: usize) -> usize {
+    WalkDir::new(root)
+        .follow_links(true)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_dir())
+        .take(budget + 1)
+        .count()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn native_watch_count(_root: &Path, _budget: usize) -> usize {
+    0
+}
+
+#[cfg(target_os = "linux")]
+fn inotify_watch_limit() -> Option<usize> {
+    fs::read_to_string("/proc/sys/fs/inotify/max_user_watches")
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn inotify_watch_limit() -> Option<usize> {
+    None
+}
+
+/// Worker loop: collects a batch of events, applies it, reports it. Ends when the watcher
+/// (and with it the sending side of the channel) is dropped.
+fn apply_events(
+    scan_id: u64,
+    scan: Arc<StoredScan>,
+    receiver: Receiver<notify::Result<Event>>,
+    app_handle: AppHandle,
+) {
+    while let Ok(first) = receiver.recv() {
+        let mut batch = vec![first];
+        let deadline = Instant::now() + BATCH_WINDOW;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match receiver.recv_timeout(remaining) {
+                Ok(event) => batch.push(event),
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        let mut paths = BTreeSet::new();
+        let mut rescan_needed = false;
+        for event in batch.into_iter().flatten() {
+            rescan_needed |= event.need_rescan();
+            if !matches!(event.kind, EventKind::Access(_)) {
+                paths.extend(event.paths);
+            }
+        }
+        if paths.is_empty() && !rescan_needed {
+            continue;
+        }
+
+        let changed = apply_paths(&scan, &paths);
+        let result = scan.result.read().unwrap();
+        let _ = app_handle.emit(
+            "tree-updated",
+            &TreeUpdate {
+                scan_id,
+                paths: changed,
+                total_size: result.total_size,
+                file_count: result.file_count,
+                rescan_needed,
+            },
+        );
+    }
+}
+
+/// Brings every path in `paths` in line with the filesystem and returns those that changed.
+fn apply_paths(scan: &StoredScan, paths: &BTreeSet<PathBuf>) -> Vec<String> {
+    let mut refresh: Vec<&Path> = Vec::new();
+    {
+        let result = scan.result.read().unwrap();
+        for path in paths {
+            // Sorted, so whatever lies inside a path comes right after it. Entries read again
+            // or removed take everything inside along with them
+            if refresh.last().is_some_and(|outer| path.starts_with(outer)) {
+                continue;
+            }
+            // Changes inside a known directory arrive as events of their own; only a directory
+            // that is new to the tree needs scanning
+            if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir())
+                && find_node(&result.root, path).is_some()
+            {
+                continue;
+            }
+            refresh.push(path);
+        }
+    }
+
+    scan.refresh(&refresh)
+        .into_iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect()
+}
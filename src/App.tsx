@@ -13,6 +13,30 @@ import { FileListView } from "./components/FileListView";
 import { ContextMenu } from "./components/ContextMenu";
 import { ThemeToggle } from "./components/ThemeToggle";
 
+const formatBytes = (bytes: number): string => {
+  if (bytes === 0) return '0 B';
+  const k = 1024;
+  const sizes = ['B', 'KB', 'MB', 'GB', 'TB'];
+  const i = Math.floor(Math.log(bytes) / Math.log(k));
+  return parseFloat((bytes / Math.pow(k, i)).toFixed(1)) + ' ' + sizes[i];
+};
+
+// Mirrors the backend's DeleteError, which is tagged by `kind`
+function describeDeleteError(error: any): string {
+  switch (error?.kind) {
+    case 'protected':
+      return error.path === error.protected_path
+        ? `${error.path} is protected`
+        : `${error.path} contains the protected path ${error.protected_path}`;
+    case 'not_found':
+      return `${error.path} does not exist`;
+    case 'io':
+      return `${error.path}: ${error.message}`;
+    default:
+      return String(error);
+  }
+}
+
 function App() {
   const [scanning, setScanning] = useState(false);
   const [scanResults, setScanResults] = useState<any>(null);
@@ -20,6 +44,8 @@ function App() {
   const [searchTerm, setSearchTerm] = useState('');
   const [scanProgress, setScanProgress] = useState<any>(null);
   const [scanPath, setScanPath] = useState('');
+  const [activeScanId, setActiveScanId] = useState<number | null>(null);
+  const [canUndo, setCanUndo] = useState(false);
   const [switchingView, setSwitchingView] = useState(false);
   const [contextMenu, setContextMenu] = useState<{
     visible: boolean;
@@ -35,8 +61,13 @@ function App() {
       setScanProgress(event.payload);
     });
 
+    const unlistenStarted = listen<number>('scan-started', (event) => {
+      setActiveScanId(event.payload);
+    });
+
     return () => {
       unlisten.then(f => f());
+      unlistenStarted.then(f => f());
     };
   }, []);
 
@@ -48,6 +79,10 @@ function App() {
       try {
         const pathToScan = scanPath || (navigator.userAgent.includes('Windows') ? 'C:\\Users' : '/home');
         const results = await invoke("scan_directory", { path: pathToScan });
+        // The backend keeps each finished tree around for paging; drop the one we are replacing
+        if (scanResults?.scan_id) {
+          invoke('release_scan', { scanId: scanResults.scan_id }).catch(() => {});
+        }
         setScanResults(results);
       } catch (realError) {
         console.log("Real scan failed, using demo data:", realError);
@@ -104,10 +139,33 @@ function App() {
     } finally {
       setScanning(false);
       setScanProgress(null);
+      setActiveScanId(null);
+    }
+  }
+
+  async function cancelScan() {
+    if (activeScanId === null) return;
+    try {
+      await invoke('cancel_scan', { scanId: activeScanId });
+    } catch (error) {
+      console.error('Failed to cancel scan:', error);
     }
   }
 
+  // The scan result stops a few levels down; the list view pages in deeper directories on expand
+  async function loadChildren(file: any) {
+    const page: any = await invoke('get_children', {
+      scanId: scanResults.scan_id,
+      path: file.path,
+      offset: 0,
+      limit: 20,
+    });
+    return page.children;
+  }
+
   const handleContextMenu = (file: any, event: React.MouseEvent) => {
+    // Aggregated "other items" nodes point at their parent, so file actions on them would be wrong
+    if (file.kind === 'other') return;
     setContextMenu({
       visible: true,
       x: event.clientX,
@@ -126,18 +184,70 @@ function App() {
     }
   };
 
-  const handleDeleteFile = async () => {
+  const handleDeleteFile = async (permanent: boolean) => {
     if (contextMenu.file) {
-      if (confirm(`Are you sure you want to delete "${contextMenu.file.name}"?`)) {
-        try {
-          await invoke('delete_file_or_folder', { path: contextMenu.file.path });
-          // Trigger a rescan to update the UI
-          startScan();
-        } catch (error) {
-          console.error('Failed to delete file:', error);
-          alert('Failed to delete file: ' + error);
+      try {
+        // Check first what the deletion involves, then ask with the numbers in front of the user
+        const plan: any = await invoke('plan_deletion', {
+          paths: [contextMenu.file.path],
+          mode: permanent ? 'permanent' : 'trash',
+        });
+        const item = plan.items[0];
+        if (item.protected) {
+          alert('Cannot delete: ' + describeDeleteError({ kind: 'protected', ...item.protected }));
+          return;
+        }
+
+        const summary = `${formatBytes(plan.total_bytes)} in ${plan.total_files} files and ${plan.total_dirs} folders`;
+        const warning = item.problem_count > 0
+          ? `\n\n${item.problem_count} entries may not be removable, e.g. ${item.problems[0].path}: ${item.problems[0].message}`
+          : '';
+        const question = permanent
+          ? `Permanently delete "${contextMenu.file.name}" (${summary})? This cannot be undone.`
+          : `Move "${contextMenu.file.name}" (${summary}) to the trash?`;
+        if (!confirm(question + warning)) return;
+
+        const report: any = await invoke('execute_deletion', { planId: plan.plan_id });
+        const failure = report.results.find((result: any) => result.status === 'failed');
+        if (failure) {
+          alert('Failed to delete file: ' + describeDeleteError(failure.error));
+        }
+        if (report.audit_error) {
+          alert(report.audit_error);
         }
+        if (!permanent && report.deleted > 0) {
+          setCanUndo(true);
+        }
+        // Trigger a rescan to update the UI
+        startScan();
+      } catch (error) {
+        console.error('Failed to delete file:', error);
+        alert('Failed to delete file: ' + error);
+      }
+    }
+  };
+
+  const handleUndo = async () => {
+    try {
+      let report: any = await invoke('undo_last_operation', {});
+      if (report.conflicts.length > 0) {
+        const names = report.conflicts.map((c: any) => `${c.path} → ${c.suggested_path}`).join('\n');
+        if (!confirm(`Some original locations are in use again. Restore under new names?\n\n${names}`)) return;
+        report = await invoke('undo_last_operation', { renameConflicts: true });
+      }
+      if (report.failed.length > 0) {
+        alert('Could not restore: ' + report.failed.map((f: any) => `${f.path}: ${f.message}`).join('\n'));
       }
+      if (report.audit_error) {
+        alert(report.audit_error);
+      }
+      const history: any[] = await invoke('get_operation_history');
+      setCanUndo(history.length > 0);
+      startScan();
+    } catch (error) {
+      console.error('Failed to undo:', error);
+      alert('Failed to undo: ' + error);
+      setCanUndo(false);
     }
   };
 
@@ -246,6 +356,23 @@ Type: ${contextMenu.file.is_dir ? 'Directory' : 'File'}
               >
                 {scanning ? "Scanning..." : "Start Scan"}
               </Button>
+
+              {scanning && (
+                <Button
+                  variant="outline"
+                  onClick={cancelScan}
+                  disabled={activeScanId === null}
+                  className="w-full"
+                >
+                  Cancel Scan
+                </Button>
+              )}
+
+              {canUndo && !scanning && (
+                <Button variant="outline" onClick={handleUndo} className="w-full">
+                  Undo Last Delete
+                </Button>
+              )}
               
               {scanResults && (
                 <>
@@ -253,6 +380,9 @@ Type: ${contextMenu.file.is_dir ? 'Directory' : 'File'}
                     <p>Files scanned: {scanResults.file_count || 0}</p>
                     <p>Total size: {scanResults.total_size ? `${(scanResults.total_size / 1024 / 1024 / 1024).toFixed(2)} GB` : "0 B"}</p>
                     <p>Errors: {scanResults.error_count || 0}</p>
+                    {scanResults.cancelled && (
+                      <p className="text-amber-600 dark:text-amber-400">Scan was cancelled - results are partial</p>
+                    )}
                   </div>
                   
                   <div className="space-y-2">
@@ -421,7 +551,11 @@ Type: ${contextMenu.file.is_dir ? 'Directory' : 'File'}
                           <div className="text-sm text-slate-600 dark:text-slate-400">
                             {scanProgress.files_processed.toLocaleString()} files • {(scanProgress.total_size_so_far / 1024 / 1024 / 1024).toFixed(2)} GB
                           </div>
-                          <Progress value={Math.min(scanProgress.files_processed / 1000, 100)} className="w-full max-w-md mx-auto" />
+                          <div className="text-xs text-slate-500 dark:text-slate-500">
+                            {scanProgress.phase} • {Math.round(scanProgress.files_per_sec ?? 0).toLocaleString()} files/s
+                            {scanProgress.eta_ms != null && ` • ~${Math.ceil(scanProgress.eta_ms / 1000)}s left`}
+                          </div>
+                          <Progress value={scanProgress.percent_complete ?? Math.min(scanProgress.files_processed / 1000, 100)} className="w-full max-w-md mx-auto" />
                         </>
                       )}
                     </div>
@@ -455,6 +589,7 @@ Type: ${contextMenu.file.is_dir ? 'Directory' : 'File'}
                       data={scanResults.root} 
                       onNodeClick={(data) => console.log('Clicked:', data)}
                       onContextMenu={handleContextMenu}
+                      onLoadChildren={scanResults.scan_id ? loadChildren : undefined}
                     />
                   )
                 ) : (
@@ -509,7 +644,8 @@ Type: ${contextMenu.file.is_dir ? 'Directory' : 'File'}
         y={contextMenu.y}
         onClose={() => setContextMenu({ ...contextMenu, visible: false })}
         onOpenInExplorer={handleOpenInExplorer}
-        onDelete={handleDeleteFile}
+        onDelete={() => handleDeleteFile(false)}
+        onDeletePermanently={() => handleDeleteFile(true)}
         onCopyPath={handleCopyPath}
         onShowInfo={handleShowInfo}
         fileName={contextMenu.file?.name || ''}
@@ -1,5 +1,5 @@
 import React, { useEffect, useRef } from 'react';
-import { FolderOpen, Trash2, Copy, Info } from 'lucide-react';
+import { FolderOpen, Trash, Trash2, Copy, Info } from 'lucide-react';
 
 interface ContextMenuProps {
   x: number;
@@ -8,6 +8,7 @@ interface ContextMenuProps {
   onClose: () => void;
   onOpenInExplorer: () => void;
   onDelete: () => void;
+  onDeletePermanently: () => void;
   onCopyPath: () => void;
   onShowInfo: () => void;
   fileName: string;
@@ -21,6 +22,7 @@ export const ContextMenu: React.FC<ContextMenuProps> = ({
   onClose,
   onOpenInExplorer,
   onDelete,
+  onDeletePermanently,
   onCopyPath,
   onShowInfo,
   fileName,
@@ -71,9 +73,14 @@ export const ContextMenu: React.FC<ContextMenuProps> = ({
       onClick: onShowInfo,
     },
     {
-      label: `Delete ${isDirectory ? 'folder' : 'file'}`,
-      icon: <Trash2 className="h-4 w-4" />,
+      label: 'Move to trash',
+      icon: <Trash className="h-4 w-4" />,
       onClick: onDelete,
+    },
+    {
+      label: `Delete ${isDirectory ? 'folder' : 'file'} permanently`,
+      icon: <Trash2 className="h-4 w-4" />,
+      onClick: onDeletePermanently,
       dangerous: true,
     },
   ];
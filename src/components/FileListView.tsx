@@ -7,6 +7,8 @@ interface FileInfo {
   path: string;
   size: number;
   is_dir: boolean;
+  /** Number of direct children, also when `children` was left out of the scan result */
+  child_count?: number;
   children: FileInfo[];
 }
 
@@ -14,6 +16,8 @@ interface FileListViewProps {
   data: FileInfo;
   onNodeClick?: (data: any) => void;
   onContextMenu?: (data: any, event: React.MouseEvent) => void;
+  /** Fetches the children of a directory that is deeper than the scan result goes */
+  onLoadChildren?: (file: FileInfo) => Promise<FileInfo[]>;
 }
 
 const formatBytes = (bytes: number): string => {
@@ -29,8 +33,30 @@ const FileRow: React.FC<{
   depth: number;
   onNodeClick?: (data: any) => void;
   onContextMenu?: (data: any, event: React.MouseEvent) => void;
-}> = ({ file, depth, onNodeClick, onContextMenu }) => {
+  onLoadChildren?: (file: FileInfo) => Promise<FileInfo[]>;
+}> = ({ file, depth, onNodeClick, onContextMenu, onLoadChildren }) => {
   const [expanded, setExpanded] = React.useState(depth < 2);
+  const [loadedChildren, setLoadedChildren] = React.useState<FileInfo[] | null>(null);
+  const [loading, setLoading] = React.useState(false);
+
+  const children = file.children.length > 0 ? file.children : loadedChildren ?? [];
+  // Directories past the scan result's depth arrive without children but still count them
+  const canExpand = file.is_dir && (file.children.length > 0 || (file.child_count ?? 0) > 0);
+
+  const toggleExpanded = async () => {
+    if (!expanded && children.length === 0 && onLoadChildren && !loading) {
+      setLoading(true);
+      try {
+        setLoadedChildren(await onLoadChildren(file));
+      } catch (error) {
+        console.error('Failed to load children:', error);
+        return;
+      } finally {
+        setLoading(false);
+      }
+    }
+    setExpanded(!expanded);
+  };
 
   const handleContextMenu = (e: React.MouseEvent) => {
     e.preventDefault();
@@ -48,14 +74,14 @@ const FileRow: React.FC<{
         onContextMenu={handleContextMenu}
       >
         <div className="flex items-center flex-1 min-w-0">
-          {file.is_dir && file.children.length > 0 && (
+          {canExpand && (
             <Button
               variant="ghost"
               size="sm"
               className="p-0 h-4 w-4 mr-2"
               onClick={(e) => {
                 e.stopPropagation();
-                setExpanded(!expanded);
+                toggleExpanded();
               }}
             >
               <ChevronRight
@@ -65,7 +91,7 @@ const FileRow: React.FC<{
               />
             </Button>
           )}
-          {!file.is_dir || file.children.length === 0 ? (
+          {!canExpand ? (
             <div className="w-4 mr-2" />
           ) : null}
           
@@ -108,9 +134,9 @@ const FileRow: React.FC<{
         </div>
       </div>
       
-      {expanded && file.is_dir && file.children.length > 0 && (
+      {expanded && children.length > 0 && (
         <>
-          {file.children
+          {[...children]
             .sort((a, b) => b.size - a.size)
             .slice(0, 20)
             .map((child, index) => (
@@ -120,6 +146,7 @@ const FileRow: React.FC<{
                 depth={depth + 1}
                 onNodeClick={onNodeClick}
                 onContextMenu={onContextMenu}
+                onLoadChildren={onLoadChildren}
               />
             ))}
         </>
@@ -128,7 +155,7 @@ const FileRow: React.FC<{
   );
 };
 
-export const FileListView: React.FC<FileListViewProps> = React.memo(({ data, onNodeClick, onContextMenu }) => {
+export const FileListView: React.FC<FileListViewProps> = React.memo(({ data, onNodeClick, onContextMenu, onLoadChildren }) => {
   if (!data) {
     return (
       <div className="w-full h-full flex items-center justify-center">
@@ -159,6 +186,7 @@ export const FileListView: React.FC<FileListViewProps> = React.memo(({ data, onN
           depth={0}
           onNodeClick={onNodeClick}
           onContextMenu={onContextMenu}
+          onLoadChildren={onLoadChildren}
         />
       </div>
     </div>